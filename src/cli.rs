@@ -0,0 +1,586 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use gtk::glib::ExitCode;
+use log::{debug, error};
+
+use crate::config::Settings;
+use crate::models::catalog::IndexCatalog;
+use crate::models::document::DocumentReader;
+use crate::models::export::{write_results, ExportFormat};
+use crate::models::index_job::{run_index_job, IndexFilter, IndexJobHandle, IndexProgress};
+use crate::models::index_model::StoredIndexModel;
+use crate::models::index_rules::{parse_patterns, IndexRules};
+use crate::models::search_model::{attach_snippets, rank};
+use crate::utils::{format_bytes, format_duration, format_timestamp, write_json_atomic};
+
+const USAGE: &str = "usage:
+    rustindexer [gtk options]                   start the graphical interface, see --help
+    rustindexer index <dir> [--out <file>] [--full] [--include <globs>] [--exclude <globs>]
+                      [--no-ignore] [--hidden] [--max-size <MB>] [--max-depth <N>] [--no-archives]
+                                                index a folder, into the catalog or a json file.
+                                                Only changed files are processed again unless
+                                                --full is given. The rules default to the ones of
+                                                the previous index of the folder. 0 means no
+                                                limit for --max-size and --max-depth, and
+                                                --max-depth 1 only indexes the folder itself
+    rustindexer search <query> [--index <file|id>] [--limit N] [--json | --format csv|jsonl|markdown]
+    rustindexer info <file|id>                  show what an index contains
+    rustindexer list                            list the indexes of the catalog
+    rustindexer help                            show this message";
+
+/// the options each subcommand accepts, any other first argument is left to the gtk
+/// application
+const SUBCOMMANDS: [(&str, &[&str]); 5] = [
+    (
+        "index",
+        &[
+            "--out",
+            "--full",
+            "--include",
+            "--exclude",
+            "--no-ignore",
+            "--hidden",
+            "--max-size",
+            "--max-depth",
+            "--no-archives",
+        ],
+    ),
+    ("search", &["--index", "--limit", "--json", "--format"]),
+    ("info", &[]),
+    ("list", &[]),
+    ("help", &[]),
+];
+
+/// a subcommand given on the command line, parsed from std::env::args()
+#[derive(Debug)]
+pub enum Command {
+    Index {
+        dir: PathBuf,
+        out: Option<PathBuf>,
+        full: bool,
+        rules: RuleOptions,
+    },
+    Search {
+        query: String,
+        index: Option<String>,
+        limit: Option<usize>,
+        json: bool,
+        format: Option<ExportFormat>,
+    },
+    Info {
+        index: String,
+    },
+    List,
+    Help,
+}
+
+/// the index options changing the IndexRules of the folder
+#[derive(Debug, Default)]
+pub struct RuleOptions {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    no_ignore: bool,
+    hidden: bool,
+    /// bytes, given in MB on the command line
+    max_size: Option<u64>,
+    max_depth: Option<usize>,
+    no_archives: bool,
+}
+
+impl RuleOptions {
+    /// `rules` with the options given, --include replaces the include patterns while
+    /// --exclude adds to the excluded ones
+    fn apply(&self, mut rules: IndexRules) -> IndexRules {
+        if !self.include.is_empty() {
+            rules.include = self.include.clone();
+        }
+        rules.exclude.extend(self.exclude.iter().cloned());
+        if self.no_ignore {
+            rules.respect_ignore_files = false;
+        }
+        if self.hidden {
+            rules.include_hidden = true;
+        }
+        if let Some(max_size) = self.max_size {
+            rules.max_file_size = max_size;
+        }
+        if let Some(max_depth) = self.max_depth {
+            rules.max_depth = max_depth;
+        }
+        if self.no_archives {
+            rules.descend_archives = false;
+        }
+        rules
+    }
+}
+
+/// parses the arguments (without the program name). Ok(None) means no subcommand was given
+/// and the gtk application should start with the arguments, e.g. its own options.
+pub fn parse_args(args: &[String]) -> Result<Option<Command>, String> {
+    let Some((subcommand, rest)) = args.split_first() else {
+        return Ok(None);
+    };
+    let Some((_, options)) = SUBCOMMANDS
+        .iter()
+        .find(|(name, _)| *name == subcommand.as_str())
+    else {
+        return Ok(None);
+    };
+    let mut positional = Vec::new();
+    let mut out = None;
+    let mut index = None;
+    let mut limit = None;
+    let mut json = false;
+    let mut format = None;
+    let mut full = false;
+    let mut rules = RuleOptions::default();
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        if arg.starts_with("--") && !options.contains(&arg.as_str()) {
+            let known = SUBCOMMANDS
+                .iter()
+                .any(|(_, options)| options.contains(&arg.as_str()));
+            return Err(if known {
+                format!("{} can't be used with {}", arg, subcommand)
+            } else {
+                format!("unknown option {}", arg)
+            });
+        }
+        match arg.as_str() {
+            "--out" => out = Some(PathBuf::from(expect_value(&mut iter, "--out")?)),
+            "--index" => index = Some(expect_value(&mut iter, "--index")?),
+            "--limit" => limit = Some(expect_number(&mut iter, "--limit")?),
+            "--json" => json = true,
+            "--full" => full = true,
+            "--include" => rules
+                .include
+                .extend(parse_patterns(&expect_value(&mut iter, "--include")?)),
+            "--exclude" => rules
+                .exclude
+                .extend(parse_patterns(&expect_value(&mut iter, "--exclude")?)),
+            "--no-ignore" => rules.no_ignore = true,
+            "--hidden" => rules.hidden = true,
+            "--no-archives" => rules.no_archives = true,
+            "--max-size" => {
+                let megabytes: u64 = expect_number(&mut iter, "--max-size")?;
+                let bytes = megabytes
+                    .checked_mul(1024 * 1024)
+                    .ok_or_else(|| format!("--max-size {} is too large", megabytes))?;
+                rules.max_size = Some(bytes);
+            }
+            "--max-depth" => rules.max_depth = Some(expect_number(&mut iter, "--max-depth")?),
+            "--format" => {
+                let value = expect_value(&mut iter, "--format")?;
+                format = Some(ExportFormat::parse(&value).ok_or_else(|| {
+                    format!("--format expects csv, jsonl or markdown, got {}", value)
+                })?);
+            }
+            other => positional.push(other.to_string()),
+        }
+    }
+    let command = match subcommand.as_str() {
+        "index" => Command::Index {
+            dir: PathBuf::from(single_positional(positional, "index", "<dir>")?),
+            out,
+            full,
+            rules,
+        },
+        "search" if json && format.is_some() => {
+            return Err(String::from("--json and --format can't be used together"))
+        }
+        "search" if !positional.is_empty() => Command::Search {
+            query: positional.join(" "),
+            index,
+            limit,
+            json,
+            format,
+        },
+        "search" => return Err(String::from("search expects a <query>")),
+        "info" => Command::Info {
+            index: single_positional(positional, "info", "<index>")?,
+        },
+        "list" | "help" if !positional.is_empty() => {
+            return Err(format!("{} expects no argument", subcommand))
+        }
+        "list" => Command::List,
+        "help" => Command::Help,
+        other => unreachable!("{} is not in SUBCOMMANDS", other),
+    };
+    Ok(Some(command))
+}
+
+fn expect_value<'a, I>(iter: &mut I, option: &str) -> Result<String, String>
+where
+    I: Iterator<Item = &'a String>,
+{
+    iter.next()
+        .cloned()
+        .ok_or_else(|| format!("{} expects a value", option))
+}
+
+fn expect_number<'a, I, N>(iter: &mut I, option: &str) -> Result<N, String>
+where
+    I: Iterator<Item = &'a String>,
+    N: std::str::FromStr,
+{
+    let value = expect_value(iter, option)?;
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got {}", option, value))
+}
+
+fn single_positional(positional: Vec<String>, command: &str, name: &str) -> Result<String, String> {
+    match positional.as_slice() {
+        [value] => Ok(value.clone()),
+        _ => Err(format!("{} expects exactly one {}", command, name)),
+    }
+}
+
+/// runs the subcommand given on the command line, if any. Returns None when the gtk
+/// application should start instead.
+pub fn run(args: &[String], settings: &Settings) -> Option<ExitCode> {
+    let command = match parse_args(args) {
+        Ok(Some(command)) => command,
+        Ok(None) => return None,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return Some(ExitCode::FAILURE);
+        }
+    };
+    debug!("running command {:?}", command);
+    let result = match command {
+        Command::Index {
+            dir,
+            out,
+            full,
+            rules,
+        } => index(&dir, out.as_deref(), full, &rules, settings),
+        Command::Search {
+            query,
+            index,
+            limit,
+            json,
+            format,
+        } => search(&query, index.as_deref(), limit, json, format, settings),
+        Command::Info { index } => info(&index),
+        Command::List => list(),
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => Some(ExitCode::SUCCESS),
+        Err(e) => {
+            error!("{}", e);
+            eprintln!("error: {}", e);
+            Some(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn index(
+    dir: &Path,
+    out: Option<&Path>,
+    full: bool,
+    options: &RuleOptions,
+    settings: &Settings,
+) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.to_string_lossy()));
+    }
+    let root = dir.to_string_lossy().to_string();
+    let rules = options.apply(
+        IndexCatalog::load()
+            .find_by_root(dir)
+            .map(|entry| entry.rules(settings))
+            .unwrap_or_else(|| IndexRules::from_settings(settings)),
+    );
+    let filter = IndexFilter::new(&rules);
+    // the index being replaced, the --out file or the catalog entry of the folder
+    let previous = match out {
+        _ if full || !settings.incremental_index => None,
+        Some(out) if out.is_file() => StoredIndexModel::from_file(out).ok(),
+        Some(_) => None,
+        None => StoredIndexModel::for_root(dir),
+    };
+    let handle = IndexJobHandle::new();
+    let (index, metadata, summary) = run_index_job(
+        &root,
+        &filter,
+        previous.as_ref(),
+        &handle,
+        |progress| match progress {
+            IndexProgress::Discovered(files) => eprintln!("{} files to index", files),
+            IndexProgress::Processing(snapshot) => {
+                eprint!("\r{} / {} files", snapshot.processed + 1, snapshot.total);
+                let _ = io::stderr().flush();
+            }
+            _ => {}
+        },
+    )
+    .ok_or_else(|| String::from("indexing was cancelled"))?;
+    eprintln!();
+    match out {
+        Some(out) => {
+            write_json_atomic(&index, out).map_err(|e| e.to_string())?;
+            metadata.save(out).map_err(|e| e.to_string())?;
+            println!("index written to {}", out.to_string_lossy());
+        }
+        None => {
+            let entry =
+                IndexCatalog::store(dir, &index, &metadata, &rules).map_err(|e| e.to_string())?;
+            println!(
+                "index stored in the catalog as {} ({})",
+                entry.name, entry.id
+            );
+        }
+    }
+    println!(
+        "indexed {} files ({}) in {}",
+        summary.files,
+        format_bytes(summary.bytes),
+        format_duration(summary.elapsed)
+    );
+    if let Some(changes) = summary.changes {
+        println!("{}", changes);
+    }
+    if summary.skipped > 0 {
+        println!("{} binary or unreadable files skipped", summary.skipped);
+    }
+    Ok(())
+}
+
+/// `index` is either a path to an index file or the id or name of a catalog entry
+fn load_index(index: Option<&str>) -> Result<StoredIndexModel, String> {
+    let Some(index) = index else {
+        return StoredIndexModel::load_last()?
+            .ok_or_else(|| String::from("no index yet, run `rustindexer index <dir>` first"));
+    };
+    let path = Path::new(index);
+    if path.is_file() {
+        return StoredIndexModel::from_file(path);
+    }
+    let catalog = IndexCatalog::load();
+    let entry = catalog
+        .find(index)
+        .or_else(|| catalog.entries.iter().find(|entry| entry.name == index))
+        .ok_or_else(|| format!("{} is neither an index file nor a catalog entry", index))?;
+    StoredIndexModel::open(entry)
+}
+
+fn search(
+    query: &str,
+    index: Option<&str>,
+    limit: Option<usize>,
+    json: bool,
+    format: Option<ExportFormat>,
+    settings: &Settings,
+) -> Result<(), String> {
+    let model = load_index(index)?;
+    let limit = limit.unwrap_or(settings.result_limit);
+    let reader = DocumentReader::default();
+    let mut ranking = rank(&model.data, &model.metadata, query, limit, &reader)
+        .map_err(|e| format!("invalid query: {}", e))?;
+    if let Some(format) = format {
+        let count = ranking.hits.len();
+        attach_snippets(&mut ranking.hits, query, count, &reader);
+        write_results(&ranking.hits, format, &mut io::stdout().lock())
+            .map_err(|e| e.to_string())?;
+    } else if json {
+        let output = serde_json::to_string_pretty(&ranking.hits).map_err(|e| e.to_string())?;
+        println!("{}", output);
+    } else {
+        eprintln!("{} of {} results", ranking.hits.len(), ranking.total);
+        for hit in ranking.hits {
+            println!("{:.4}\t{}", hit.score, hit.path.to_string_lossy());
+        }
+    }
+    Ok(())
+}
+
+fn info(index: &str) -> Result<(), String> {
+    let model = load_index(Some(index))?;
+    println!("name:      {}", model.name);
+    println!("id:        {}", model.id);
+    println!("documents: {}", model.data.index.len());
+    if let Some(entry) = IndexCatalog::load().find(&model.id) {
+        println!("root:      {}", entry.root.to_string_lossy());
+        println!("created:   {}", format_timestamp(entry.created));
+        println!("updated:   {}", format_timestamp(entry.updated));
+        println!("size:      {}", format_bytes(entry.size_on_disk));
+    }
+    Ok(())
+}
+
+fn list() -> Result<(), String> {
+    let catalog = IndexCatalog::load();
+    if catalog.entries.is_empty() {
+        println!("no index yet, run `rustindexer index <dir>` first");
+    }
+    for entry in &catalog.entries {
+        println!(
+            "{}\t{}\t{} documents\t{}\tupdated {}",
+            entry.id,
+            entry.name,
+            entry.document_count,
+            entry.root.to_string_lossy(),
+            format_timestamp(entry.updated)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Command>, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn no_arguments_start_the_gui() {
+        assert!(matches!(parse(&[]), Ok(None)));
+    }
+
+    #[test]
+    fn index_with_rule_options() {
+        let Ok(Some(Command::Index {
+            dir,
+            out,
+            full,
+            rules,
+        })) = parse(&[
+            "index",
+            "docs",
+            "--out",
+            "docs.json",
+            "--full",
+            "--exclude",
+            "*.log, target/",
+            "--max-size",
+            "10",
+            "--no-archives",
+        ])
+        else {
+            panic!("expected an index command");
+        };
+        assert_eq!(dir, PathBuf::from("docs"));
+        assert_eq!(out, Some(PathBuf::from("docs.json")));
+        assert!(full);
+        assert_eq!(rules.exclude, ["*.log", "target/"]);
+        assert_eq!(rules.max_size, Some(10 * 1024 * 1024));
+        assert!(rules.no_archives);
+        assert!(!rules.hidden);
+    }
+
+    #[test]
+    fn search_joins_the_words_of_the_query() {
+        let Ok(Some(Command::Search {
+            query,
+            index,
+            limit,
+            json,
+            format,
+        })) = parse(&["search", "rust", "gtk", "--limit", "5", "--format", "md"])
+        else {
+            panic!("expected a search command");
+        };
+        assert_eq!(query, "rust gtk");
+        assert_eq!(index, None);
+        assert_eq!(limit, Some(5));
+        assert!(!json);
+        assert_eq!(format, Some(ExportFormat::Markdown));
+    }
+
+    #[test]
+    fn other_commands() {
+        assert!(matches!(
+            parse(&["info", "abc"]),
+            Ok(Some(Command::Info { index })) if index == "abc"
+        ));
+        assert!(matches!(parse(&["list"]), Ok(Some(Command::List))));
+        assert!(matches!(parse(&["help"]), Ok(Some(Command::Help))));
+    }
+
+    #[test]
+    fn other_arguments_are_left_to_the_gui() {
+        for args in [
+            &["--help"][..],
+            &["--gapplication-service"],
+            &["-h"],
+            &["notes.txt"],
+        ] {
+            assert!(matches!(parse(args), Ok(None)), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn options_of_another_subcommand_are_rejected() {
+        assert_eq!(
+            parse(&["search", "a", "--full"]).unwrap_err(),
+            "--full can't be used with search"
+        );
+        assert_eq!(
+            parse(&["index", "docs", "--limit", "5"]).unwrap_err(),
+            "--limit can't be used with index"
+        );
+        assert_eq!(
+            parse(&["list", "--json"]).unwrap_err(),
+            "--json can't be used with list"
+        );
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        for args in [
+            &["index"][..],
+            &["index", "a", "b"],
+            &["index", "docs", "--out"],
+            &["index", "docs", "--max-depth", "deep"],
+            &["index", "docs", "--max-size", "-1"],
+            &["index", "docs", "--max-size", "17592186044416"],
+            &["search"],
+            &["search", "a", "--json", "--format", "csv"],
+            &["search", "a", "--format", "xml"],
+            &["search", "a", "--unknown"],
+            &["info"],
+            &["list", "extra"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn rule_options_change_the_previous_rules() {
+        let options = RuleOptions {
+            include: vec![String::from("*.md")],
+            exclude: vec![String::from("*.tmp")],
+            no_ignore: true,
+            max_size: Some(2 * 1024 * 1024),
+            ..Default::default()
+        };
+        let previous = IndexRules::default();
+        let rules = options.apply(previous.clone());
+        assert_eq!(rules.include, ["*.md"]);
+        assert_eq!(rules.exclude.len(), previous.exclude.len() + 1);
+        assert!(!rules.respect_ignore_files);
+        assert_eq!(rules.max_file_size, 2 * 1024 * 1024);
+        assert_eq!(rules.max_depth, previous.max_depth);
+    }
+
+    #[test]
+    fn zero_limits_are_kept_as_no_limit() {
+        let Ok(Some(Command::Index { rules, .. })) =
+            parse(&["index", "docs", "--max-size", "0", "--max-depth", "0"])
+        else {
+            panic!("expected an index command");
+        };
+        let rules = rules.apply(IndexRules::default());
+        assert_eq!(rules.max_file_size, 0);
+        assert_eq!(rules.max_depth, 0);
+    }
+}
@@ -1,20 +1,115 @@
-use log::LevelFilter::{Debug, Error, Info, Warn};
+use std::{fs, path::PathBuf};
+
+use gtk::glib;
+use log::LevelFilter::{self, Debug, Error, Info, Trace, Warn};
+use serde::{Deserialize, Serialize};
+
+use crate::types::StandardResult;
+
 pub const APP_ID: &str = "org.gtk-rs.termirust";
 pub const APP_NAME: &str = "IndexRust";
 pub const APP_WINDOW_WIDTH: i32 = 350;
 pub const APP_WINDOW_HEIGHT: i32 = 500;
 pub const MIN_SCROLL_WINDOW_HEIGHT: i32 = 400;
-pub const INDEX_FOLDER: &str = "/home/ekla/Documents/";
-pub fn set_log_level(level: &str) {
-    let log_level = match level {
+pub const DEFAULT_INDEX_PATH: &str = "data/_index-index.json";
+pub const LOG_LEVELS: [&str; 4] = ["debug", "info", "warn", "error"];
+
+pub fn parse_log_level(level: &str) -> LevelFilter {
+    match level {
         "debug" => Debug,
         "info" => Info,
         "warn" => Warn,
         "error" => Error,
         _ => Error,
-    };
-
+    }
+}
+/// the logger lets every record through and log::max_level() does the filtering, so the
+/// level can be changed while the application runs
+pub fn init_logger(level: &str) {
     env_logger::Builder::from_default_env()
-        .filter_level(log_level)
+        .filter_level(Trace)
         .init();
+    set_log_level(level);
+}
+pub fn set_log_level(level: &str) {
+    log::set_max_level(parse_log_level(level));
+}
+
+/// User settings stored as TOML in $XDG_CONFIG_HOME/IndexRust/config.toml. Missing keys take
+/// their default value so an older config file keeps working.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// folder the browse dialog opens on
+    pub index_root: PathBuf,
+    /// where the catalog and the index files are stored
+    pub storage_dir: PathBuf,
+    pub log_level: String,
+    /// glob patterns of files that are never indexed, e.g. "**/target/**"
+    pub excluded_patterns: Vec<String>,
+    /// files bigger than this are skipped while indexing, 0 means no limit
+    pub max_file_size: u64,
+    /// re-indexing a folder only processes the files changed since its last index
+    pub incremental_index: bool,
+    /// whether the folder of the active index is watched for changes at startup
+    pub watch_changes: bool,
+    /// maximum number of results kept for a search, browsed page by page
+    pub result_limit: usize,
+    /// number of results per page
+    pub page_size: usize,
+    /// a "load more" button appends the next page instead of replacing the current one
+    pub load_more: bool,
+    pub window_width: i32,
+    pub window_height: i32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            index_root: glib::home_dir(),
+            storage_dir: glib::user_data_dir().join(APP_NAME),
+            log_level: String::from("info"),
+            excluded_patterns: vec![
+                String::from("**/.git/**"),
+                String::from("**/target/**"),
+                String::from("**/node_modules/**"),
+            ],
+            max_file_size: 10 * 1024 * 1024,
+            incremental_index: true,
+            watch_changes: true,
+            result_limit: 1000,
+            page_size: 50,
+            load_more: false,
+            window_width: APP_WINDOW_WIDTH,
+            window_height: APP_WINDOW_HEIGHT,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        glib::user_config_dir().join(APP_NAME).join("config.toml")
+    }
+    /// reads the config file, falling back to the defaults when it is missing or invalid.
+    /// This runs before the logger is set up so problems are printed on stderr.
+    pub fn load() -> Self {
+        let path = Self::path();
+        match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("invalid config file {:?}, using defaults: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+    pub fn save(&self) -> StandardResult {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("toml.tmp");
+        fs::write(&tmp_path, toml::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
 }
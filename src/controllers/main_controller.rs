@@ -1,36 +1,46 @@
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
+use std::path::Path;
 use std::{cell::RefCell, path::PathBuf, rc::Rc, thread};
 
-use gtk::gio::{File, FileInfo};
-use gtk::glib::GString;
-use gtk::{glib::SignalHandlerId, prelude::*, ApplicationWindow, Button, Label, Window};
-use search_engine::index::Index;
+use gtk::gio::{Cancellable, File, FileInfo, ListStore};
+use gtk::glib::{ControlFlow, GString, MainContext, Priority};
+use gtk::{
+    glib::SignalHandlerId, prelude::*, Application, ApplicationWindow, Button, Label, Window,
+};
+use gtk::{FileDialog, FileFilter};
 use search_engine::types::WrapInRcRefCell;
-use search_engine::utils::walk_dir;
 
-use crate::config::INDEX_FOLDER;
+use crate::config::{set_log_level, Settings};
+use crate::controllers::result_controller::ResultController;
+use crate::controllers::search_controller::SearchController;
+use crate::controllers::watch_controller::WatchController;
+use crate::models::catalog::IndexCatalog;
+use crate::models::index_job::{run_index_job, IndexFilter, IndexJobHandle, IndexProgress};
+use crate::models::index_rules::IndexRules;
 use crate::types::{Controller, VecInfo};
 use crate::views::browse_view;
+use crate::views::catalog_view::{CatalogAction, CatalogView};
+use crate::views::index_rules_view::IndexRulesView;
 use crate::views::main_view::{self, MainView};
+use crate::views::settings_view::SettingsView;
 use crate::{models::index_model::StoredIndexModel, views::browse_view::BrowseView};
-use log::{debug, info};
+use log::{debug, error, info};
 
 pub struct MainController {
     main_view: MainView,
     browse_view: Option<BrowseView>,
     list_handler_id: Rc<RefCell<VecDeque<Rc<RefCell<Option<SignalHandlerId>>>>>>,
     data: String,
-    index: Rc<RefCell<Index>>,
+    job: Rc<RefCell<Option<IndexJobHandle>>>,
 }
 impl Clone for MainController {
     fn clone(&self) -> Self {
         Self {
             main_view: self.main_view.clone(),
             browse_view: self.browse_view.clone(),
-            index: self.index.clone(),
             list_handler_id: self.list_handler_id.clone(),
             data: self.data.clone(),
+            job: self.job.clone(),
         }
     }
 }
@@ -39,15 +49,14 @@ impl MainController {
         let data = String::new();
         let browse_view = None;
         let main_view = main_view.clone();
-        let list = vec![String::new()];
-        let index = Rc::new(RefCell::new(Index::new(list)));
         let list_handler_id = Rc::new(RefCell::new(VecDeque::new()));
+        let job = Rc::new(RefCell::new(None));
         Self {
             main_view,
             browse_view,
-            index,
             list_handler_id,
             data,
+            job,
         }
     }
     //since we use it a lot we might want to unwrap this attribute rapidly
@@ -67,7 +76,9 @@ impl MainController {
     /// the browse_init method instantiate a BrowseView struct attribute of the main controller
     fn browse_view_init(&mut self) {
         // if self.browse_view.is_none()
-        self.browse_view = Some(BrowseView::new());
+        self.browse_view = Some(BrowseView::new(
+            &self.main_view.settings.borrow().index_root,
+        ));
     }
     fn handler_id_init(&mut self) {}
 
@@ -236,36 +247,137 @@ impl MainController {
     pub fn handle_index_clicked(&self, dynamic_path: Rc<RefCell<String>>) -> SignalHandlerId {
         let (rc_refcell_wrap_clone_self, self_cloned) = self.tuple_clones_before_closure();
         self.main_view.index_button.connect_clicked(move |_| {
-            let borrowed_path = dynamic_path.borrow_mut();
-            debug!(
-                "connect_click for indexing with path => {:?}",
-                borrowed_path
-            );
-            let list_files = walk_dir(&borrowed_path.as_str());
-            let borrowed = rc_refcell_wrap_clone_self.borrow_mut();
-            let mut borrowed_index_ref = borrowed.index.borrow_mut();
-            let index_instance = Index::new(list_files);
-            let atomic_ref_mutex = Arc::new(Mutex::new(index_instance.clone()));
-            let clone_mutex_for_thread = Arc::clone(&atomic_ref_mutex);
+            let root = dynamic_path.borrow_mut().to_string();
+            debug!("connect_click for indexing with path => {:?}", root);
+            let controller = rc_refcell_wrap_clone_self.borrow();
+            let incremental = controller.main_view.settings.borrow().incremental_index;
+            controller.review_index_job(root, incremental);
+        })
+    }
+    /// shows the rules of `root` (the ones of its previous index, or the defaults of the
+    /// settings) so they can be edited before the job starts
+    fn review_index_job(&self, root: String, incremental: bool) {
+        if self.job.borrow().is_some() {
+            debug!("an indexing job is already running");
+            return;
+        }
+        let settings = self.main_view.settings.borrow().clone();
+        let rules = IndexCatalog::load()
+            .find_by_root(Path::new(&root))
+            .map(|entry| entry.rules(&settings))
+            .unwrap_or_else(|| IndexRules::from_settings(&settings));
+        let rules_view = IndexRulesView::new();
+        rules_view.build_ui();
+        rules_view.fill(&root, &rules);
+        let self_cloned = self.clone();
+        let view_cloned = rules_view.clone();
+        rules_view.start_button.connect_clicked(move |_| {
+            view_cloned.window.close();
+            self_cloned.start_index_job(root.clone(), incremental, view_cloned.read());
+        });
+        rules_view.window.present();
+    }
+    /// indexes `root` on a worker thread, stores the result in the catalog and makes it the
+    /// active index. `incremental` reuses the previous index of the folder if there is one.
+    /// Does nothing if a job is already running.
+    fn start_index_job(&self, root: String, incremental: bool, rules: IndexRules) {
+        if self.job.borrow().is_some() {
+            debug!("an indexing job is already running");
+            return;
+        }
+        let handle = IndexJobHandle::new();
+        self.job.replace(Some(handle.clone()));
+        let filter = IndexFilter::new(&rules);
+        // progress and the finished index are sent back to the main loop since gtk widgets
+        // and Rc<RefCell<_>> cannot cross threads
+        let (sender, receiver) = MainContext::channel::<IndexProgress>(Priority::DEFAULT);
 
-            let index_task = thread::spawn(move || {
-                if let Ok(mut locked_mutex) = clone_mutex_for_thread.lock() {
-                    locked_mutex.index_all();
-                } else {
-                    eprintln!("Error locking Mutex")
+        thread::spawn(move || {
+            let previous = if incremental {
+                StoredIndexModel::for_root(Path::new(&root))
+            } else {
+                None
+            };
+            let Some((index, metadata, summary)) =
+                run_index_job(&root, &filter, previous.as_ref(), &handle, |progress| {
+                    let _ = sender.send(progress);
+                })
+            else {
+                info!("indexing of {} cancelled", root);
+                let _ = sender.send(IndexProgress::Cancelled);
+                return;
+            };
+            let model = match IndexCatalog::store(Path::new(&root), &index, &metadata, &rules) {
+                Ok(entry) => {
+                    info!("index of {} stored as {}", root, entry.id);
+                    StoredIndexModel::from_entry(index, metadata, &entry)
                 }
-            });
-
-            // if let Err(e) = index_task.join() {
-            //     eprint!("Error joining the thread of the index task: {:?}", e)
-            // }
-            *borrowed_index_ref = index_instance;
-            // *borrowed_index_ref = Index::new(list_files);
+                Err(e) => {
+                    error!("could not store the index of {}: {}", root, e);
+                    StoredIndexModel::from_index(index, metadata, &root)
+                }
+            };
+            if let Err(e) = sender.send(IndexProgress::Finished(model, summary)) {
+                error!("could not send the index back to the main loop: {}", e)
+            }
+        });
 
-            // borrowed_index_ref.index_all();
-            // let term = borrowed.index.idf_calculation("substance");
-            // println!("{:?}", term)
-        })
+        let main_view = self.main_view.clone();
+        main_view.set_indexing(true);
+        main_view.progress.start();
+        let job_ref = self.job.clone();
+        receiver.attach(None, move |progress| match progress {
+            IndexProgress::Discovered(files) => {
+                main_view.progress.set_discovered(files);
+                ControlFlow::Continue
+            }
+            IndexProgress::Processing(snapshot) => {
+                main_view.progress.update(&snapshot);
+                ControlFlow::Continue
+            }
+            IndexProgress::Finished(model, summary) => {
+                // the index we just built becomes the one searches run against
+                main_view.set_active_index(model);
+                main_view.progress.finish(&summary);
+                main_view.set_indexing(false);
+                job_ref.replace(None);
+                debug!("indexing task finished: {:?}", summary);
+                ControlFlow::Break
+            }
+            IndexProgress::Cancelled => {
+                main_view.progress.cancelled();
+                main_view.set_indexing(false);
+                job_ref.replace(None);
+                ControlFlow::Break
+            }
+        });
+    }
+    /// connects the pause and cancel buttons shown in place of the index button while a job
+    /// runs. They act on whatever job is stored in `self.job` at click time.
+    pub fn handle_job_controls(&self) {
+        let job = self.job.clone();
+        let progress = self.main_view.progress.clone();
+        self.main_view.pause_button.connect_clicked(move |button| {
+            if let Some(handle) = job.borrow().as_ref() {
+                if handle.is_paused() {
+                    handle.resume();
+                    button.set_label("pause");
+                } else {
+                    handle.pause();
+                    button.set_label("resume");
+                }
+                progress.set_paused(handle.is_paused());
+            }
+        });
+        let job = self.job.clone();
+        self.main_view.cancel_button.connect_clicked(move |button| {
+            if let Some(handle) = job.borrow().as_ref() {
+                debug!("cancelling the indexing job");
+                handle.cancel();
+                // the job stops after the file being indexed, avoid a second click meanwhile
+                button.set_sensitive(false);
+            }
+        });
     }
     fn set_index_directory_on_selection(&self, dir: &Rc<RefCell<Option<File>>>) {
         let (rc_refcell_wrap_clone_self, self_cloned) = self.tuple_clones_before_closure();
@@ -301,39 +413,201 @@ impl MainController {
     }
     ///This function defines the behaviour of the "browse" Button on click. It
 
-    pub fn handle_search_clicked(&self, button: &Button) {
-        let (rc_refcell_wrap_clone_self, _) = self.tuple_clones_before_closure();
-        button.connect_clicked(move |_| {
-            let borrowed = rc_refcell_wrap_clone_self.borrow();
-            let input_view = &borrowed.main_view.input_view;
-            let default_index = &borrowed
-                .main_view
-                .model
-                .clone()
-                .expect("stored data in data/ directory not found")
-                .data;
-            debug!("{:?}", default_index);
-            let user_input = input_view.search_entry.text();
-            input_view.output_screen.clear_buffer();
-
-            let user_input_cloned = user_input.clone();
-            let tf_idf = default_index.idf_calculation(user_input.as_str());
-            // let tf_idf = borrowed.index.borrow().idf_calculation(user_input.as_str());
-            println!("{:?}", tf_idf);
-            let mut vec_from_hash: Vec<(PathBuf, f32)> = tf_idf.into_iter().collect();
-            vec_from_hash.sort_by(|(_, v1), (_, v2)| {
-                v2.partial_cmp(v1).expect("the comparison should work fine")
+    /// query execution and rendering belong to the SearchController
+    pub fn handle_search(&self) {
+        let controller = SearchController::new(
+            &self.main_view.input_view,
+            &self.main_view.model,
+            &self.main_view.headerbar,
+            &self.main_view.settings,
+        );
+        controller.setup();
+        self.main_view
+            .connect_active_index_changed(move || controller.forget_pending_query());
+    }
+    /// opening, revealing and copying search results
+    pub fn handle_result_actions(&self) {
+        ResultController::new(&self.main_view.input_view).setup();
+    }
+    /// keeps the active index in sync with its folder while the watch switch is on, what the
+    /// watcher did not store yet is written when the application exits
+    pub fn handle_watcher(&self, app: &Application) {
+        let watcher = WatchController::new(&self.main_view, &self.job);
+        watcher.setup();
+        app.connect_shutdown(move |_| watcher.shutdown());
+    }
+    /// the "New Index" menu entry starts the same flow as the browse button
+    pub fn handle_new_index_clicked(&self) {
+        let headerbar = self.main_view.headerbar.clone();
+        let browse = self.main_view.browse.clone();
+        self.main_view
+            .headerbar
+            .new_button
+            .connect_clicked(move |_| {
+                headerbar.popover.popdown();
+                browse.emit_clicked();
             });
-
-            for x in vec_from_hash {
-                let displayed_string = String::from("\n")
-                    + x.0.to_str().expect("ok for path to be converted into &str");
-                borrowed
-                    .main_view
-                    .input_view
-                    .update_screen(displayed_string.as_str())
+    }
+    /// opens the dialog listing the indexes of the catalog
+    pub fn handle_catalog_clicked(&self) {
+        let self_cloned = self.clone();
+        let headerbar = self.main_view.headerbar.clone();
+        self.main_view
+            .headerbar
+            .indexes_button
+            .connect_clicked(move |_| {
+                headerbar.popover.popdown();
+                let catalog_view = CatalogView::new();
+                catalog_view.build_ui();
+                self_cloned.refresh_catalog_view(&catalog_view);
+                catalog_view.window.present();
+            });
+    }
+    /// opens the preferences window, saving writes the TOML config and replaces the settings
+    /// shared by the views and controllers
+    pub fn handle_settings_clicked(&self) {
+        let main_view = self.main_view.clone();
+        self.main_view
+            .headerbar
+            .settings_button
+            .connect_clicked(move |_| {
+                main_view.headerbar.popover.popdown();
+                let settings_view = SettingsView::new();
+                settings_view.build_ui();
+                settings_view.fill(&main_view.settings.borrow());
+                let view_cloned = settings_view.clone();
+                let shared = main_view.settings.clone();
+                settings_view.save_button.connect_clicked(move |_| {
+                    let settings = view_cloned.read(&shared.borrow());
+                    match settings.save() {
+                        Ok(()) => {
+                            set_log_level(&settings.log_level);
+                            IndexCatalog::set_storage_dir(&settings.storage_dir);
+                            shared.replace(settings);
+                            info!("settings saved to {:?}", Settings::path());
+                            view_cloned.window.close();
+                        }
+                        Err(e) => {
+                            error!("could not save the settings: {}", e);
+                            view_cloned
+                                .status_label
+                                .set_text(format!("could not save: {}", e).as_str());
+                        }
+                    }
+                });
+                settings_view.window.present();
+            });
+    }
+    /// lets the user pick any index json file and swaps it in as the active index
+    pub fn handle_open_index_clicked(&self) {
+        let main_view = self.main_view.clone();
+        self.main_view
+            .headerbar
+            .open_button
+            .connect_clicked(move |button| {
+                main_view.headerbar.popover.popdown();
+                let filter = FileFilter::new();
+                filter.set_name(Some("index files"));
+                filter.add_suffix("json");
+                let filters = ListStore::new::<FileFilter>();
+                filters.append(&filter);
+                let dialog = FileDialog::builder()
+                    .title("Open index")
+                    .modal(true)
+                    .filters(&filters)
+                    .build();
+                let window = button.root().and_downcast::<Window>();
+                let main_view = main_view.clone();
+                dialog.open(window.as_ref(), Cancellable::NONE, move |result| {
+                    let file = match result {
+                        Ok(file) => file,
+                        Err(e) => {
+                            debug!("no index file chosen: {}", e);
+                            return;
+                        }
+                    };
+                    let Some(path) = file.path() else {
+                        main_view.show_error(
+                            "The index could not be opened",
+                            "only local index files can be opened",
+                        );
+                        return;
+                    };
+                    match StoredIndexModel::from_file(&path) {
+                        Ok(model) => {
+                            info!("index loaded from {:?}", path);
+                            main_view.set_active_index(model)
+                        }
+                        Err(e) => {
+                            error!("{}", e);
+                            main_view.show_error("The index could not be opened", &e)
+                        }
+                    }
+                });
+            });
+    }
+    fn refresh_catalog_view(&self, catalog_view: &CatalogView) {
+        let catalog = IndexCatalog::load();
+        let active_id = self.active_index_id();
+        let (self_cloned, view_cloned) = (self.clone(), catalog_view.clone());
+        catalog_view.refresh(
+            &catalog,
+            active_id.as_deref(),
+            Rc::new(move |action| {
+                self_cloned.handle_catalog_action(action);
+                self_cloned.refresh_catalog_view(&view_cloned);
+            }),
+        );
+    }
+    fn active_index_id(&self) -> Option<String> {
+        self.main_view
+            .model
+            .borrow()
+            .as_ref()
+            .map(|model| model.id.clone())
+    }
+    fn handle_catalog_action(&self, action: CatalogAction) {
+        debug!("catalog action: {:?}", action);
+        let catalog = IndexCatalog::load();
+        match action {
+            CatalogAction::Open(id) => {
+                if let Some(entry) = catalog.find(&id) {
+                    match StoredIndexModel::open(entry) {
+                        Ok(model) => self.main_view.set_active_index(model),
+                        Err(e) => {
+                            error!("{}", e);
+                            self.main_view
+                                .show_error("The index could not be opened", &e)
+                        }
+                    }
+                }
             }
-        });
+            CatalogAction::Rename(id, name) => {
+                if let Err(e) = IndexCatalog::rename(&id, &name) {
+                    error!("could not rename the index {}: {}", id, e);
+                }
+                if let Some(model) = self.main_view.model.borrow_mut().as_mut() {
+                    if model.id == id {
+                        model.name = name;
+                    }
+                }
+                self.main_view.refresh_active_index();
+            }
+            CatalogAction::Rebuild(id) => {
+                if let Some(entry) = catalog.find(&id) {
+                    // rebuilding is the way to start over from scratch
+                    self.review_index_job(entry.root.to_string_lossy().to_string(), false);
+                }
+            }
+            CatalogAction::Delete(id) => {
+                if let Err(e) = IndexCatalog::remove(&id) {
+                    error!("could not delete the index {}: {}", id, e);
+                }
+                if self.active_index_id().as_deref() == Some(id.as_str()) {
+                    self.main_view.clear_active_index();
+                }
+            }
+        }
     }
     pub fn handle_exit_clicked(&self, button: &Button, win: &ApplicationWindow) -> SignalHandlerId {
         let clone = win.clone();
@@ -1,2 +1,4 @@
 pub mod main_controller;
+pub mod result_controller;
 pub mod search_controller;
+pub mod watch_controller;
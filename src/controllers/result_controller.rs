@@ -0,0 +1,156 @@
+use gtk::gio::{self, AppLaunchContext, Cancellable, SimpleAction, SimpleActionGroup};
+use gtk::{prelude::*, FileLauncher, Shortcut, ShortcutController, ShortcutTrigger, Window};
+use gtk::{NamedAction, ShortcutScope};
+use log::{debug, error};
+
+use crate::models::archive::{extract_entry, file_on_disk, split_entry_path};
+use crate::models::query::highlight_terms;
+use crate::models::search_model::SearchHit;
+use crate::views::search_view::SearchView;
+
+/// actions available on a search result, (name, keyboard shortcut)
+const RESULT_ACTIONS: [(&str, &str); 5] = [
+    ("open", "<Control>o"),
+    ("open-with", "<Control><Alt>o"),
+    ("reveal", "<Control><Shift>o"),
+    ("copy-path", "<Control>c"),
+    ("copy-uri", "<Control><Shift>c"),
+];
+
+/// Gives the result list its behaviour: activating a row opens the file, and the context
+/// menu entries and their shortcuts are "result.*" actions acting on the selected row.
+#[derive(Clone)]
+pub struct ResultController {
+    view: SearchView,
+}
+
+impl ResultController {
+    pub fn new(view: &SearchView) -> Self {
+        Self { view: view.clone() }
+    }
+
+    pub fn setup(&self) {
+        let group = SimpleActionGroup::new();
+        let shortcuts = ShortcutController::new();
+        shortcuts.set_scope(ShortcutScope::Local);
+        for (name, accel) in RESULT_ACTIONS {
+            let action = SimpleAction::new(name, None);
+            let controller = self.clone();
+            action.connect_activate(move |action, _| {
+                controller.run_on_selected(action.name().as_str());
+            });
+            group.add_action(&action);
+            shortcuts.add_shortcut(Shortcut::new(
+                ShortcutTrigger::parse_string(accel),
+                Some(NamedAction::new(format!("result.{}", name).as_str())),
+            ));
+        }
+        let column_view = &self.view.results.column_view;
+        column_view.insert_action_group("result", Some(&group));
+        column_view.add_controller(shortcuts);
+
+        let controller = self.clone();
+        self.view.results.connect_row_activated(move |hit| {
+            debug!("result activated: {:?}", hit);
+            controller.open(hit);
+        });
+        let controller = self.clone();
+        self.view
+            .results
+            .selection
+            .connect_selection_changed(move |_, _, _| controller.preview_selected());
+    }
+
+    /// shows the selected result in the preview pane with the query terms highlighted
+    fn preview_selected(&self) {
+        if let Some(hit) = self.view.results.selected_hit() {
+            let terms = highlight_terms(self.view.last_query.borrow().as_str());
+            self.view.preview.show_document(&hit.path, &terms);
+        }
+    }
+
+    fn run_on_selected(&self, action: &str) {
+        let Some(hit) = self.view.results.selected_hit() else {
+            debug!("no result selected for {}", action);
+            return;
+        };
+        match action {
+            "open" => self.open(&hit),
+            "open-with" => self.open_with(&hit),
+            "reveal" => self.reveal(&hit),
+            "copy-path" => self.copy(hit.path.to_string_lossy().as_ref()),
+            "copy-uri" => self.copy(gio::File::for_path(file_on_disk(&hit.path)).uri().as_str()),
+            _ => error!("unknown result action {}", action),
+        }
+    }
+
+    /// the file to hand to other applications, an entry of an archive is extracted first
+    fn file_to_open(&self, hit: &SearchHit) -> Option<gio::File> {
+        if split_entry_path(&hit.path).is_none() {
+            return Some(gio::File::for_path(&hit.path));
+        }
+        match extract_entry(&hit.path) {
+            Ok(copy) => Some(gio::File::for_path(copy)),
+            Err(e) => {
+                self.report(format!(
+                    "could not extract {}: {}",
+                    hit.path.to_string_lossy(),
+                    e
+                ));
+                None
+            }
+        }
+    }
+
+    fn open(&self, hit: &SearchHit) {
+        let Some(file) = self.file_to_open(hit) else {
+            return;
+        };
+        let uri = file.uri();
+        if let Err(e) = gio::AppInfo::launch_default_for_uri(&uri, AppLaunchContext::NONE) {
+            self.report(format!("could not open {}: {}", uri, e));
+        }
+    }
+
+    /// lets the user choose the application, through the portal when sandboxed
+    fn open_with(&self, hit: &SearchHit) {
+        let Some(file) = self.file_to_open(hit) else {
+            return;
+        };
+        let launcher = FileLauncher::new(Some(&file));
+        launcher.set_always_ask(true);
+        let controller = self.clone();
+        launcher.launch(self.window().as_ref(), Cancellable::NONE, move |result| {
+            if let Err(e) = result {
+                controller.report(format!("could not open the file: {}", e));
+            }
+        });
+    }
+
+    /// shows the archive for an entry of an archive
+    fn reveal(&self, hit: &SearchHit) {
+        let launcher = FileLauncher::new(Some(&gio::File::for_path(file_on_disk(&hit.path))));
+        let controller = self.clone();
+        launcher.open_containing_folder(self.window().as_ref(), Cancellable::NONE, move |result| {
+            if let Err(e) = result {
+                controller.report(format!("could not open the containing folder: {}", e));
+            }
+        });
+    }
+
+    fn copy(&self, text: &str) {
+        self.view.results.column_view.clipboard().set_text(text);
+        self.view
+            .status_label
+            .set_text(format!("copied {}", text).as_str());
+    }
+
+    fn window(&self) -> Option<Window> {
+        self.view.gtk_box.root().and_downcast::<Window>()
+    }
+
+    fn report(&self, message: String) {
+        error!("{}", message);
+        self.view.status_label.set_text(message.as_str());
+    }
+}
@@ -1,25 +1,389 @@
-use crate::models::index_model::{IndexModel, StoredIndexModel};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use gtk::gio::{Cancellable, ListStore};
+use gtk::glib::{ControlFlow, MainContext, Priority};
+use gtk::{glib::SignalHandlerId, prelude::*, FileDialog, FileFilter, Window};
+use log::{debug, error, info};
+
+use crate::models::document::DocumentReader;
+use crate::models::export::{write_results, ExportFormat, EXPORT_FORMATS};
+use crate::models::history::SearchHistory;
+use crate::models::search_model::{attach_snippets, run_search, SearchHit, SearchProgress};
+use crate::types::{ActiveIndex, SharedSettings};
 use crate::views::search_view::SearchView;
-use gtk::{glib::SignalHandlerId, prelude::*, Button, SearchEntry};
+use crate::widgets::menu_bar::CustomBar;
+
+/// completions shown under the search entry
+const MAX_SUGGESTIONS: usize = 8;
 
-pub struct SearchController<'a> {
-    view: &'a SearchView,
+/// Runs the queries typed in the SearchView against the active index and renders the
+/// results. The ranking itself lives in search_model so it can be used without a window.
+/// Also keeps the search history and the saved searches of the header bar menu.
+#[derive(Clone)]
+pub struct SearchController {
+    view: SearchView,
+    model: ActiveIndex,
+    headerbar: CustomBar,
+    settings: SharedSettings,
+    /// incremented for every search, the results of an older search are dropped
+    generation: Arc<AtomicU64>,
+    /// the query of the latest search started, finished or not
+    pending_query: Rc<RefCell<String>>,
+    pub history: Rc<RefCell<SearchHistory>>,
 }
 
-impl<'a> SearchController<'a> {
-    pub fn new(view: &'a SearchView) -> Self {
-        Self { view }
+impl SearchController {
+    pub fn new(
+        view: &SearchView,
+        model: &ActiveIndex,
+        headerbar: &CustomBar,
+        settings: &SharedSettings,
+    ) -> Self {
+        Self {
+            view: view.clone(),
+            model: model.clone(),
+            headerbar: headerbar.clone(),
+            settings: settings.clone(),
+            generation: Arc::new(AtomicU64::new(0)),
+            pending_query: Rc::new(RefCell::new(String::new())),
+            history: Rc::new(RefCell::new(SearchHistory::load())),
+        }
+    }
+
+    pub fn setup(&self) {
+        self.handle_activate();
+        self.handle_click_search_button();
+        self.handle_search_changed();
+        self.handle_suggestions();
+        self.handle_pin();
+        self.handle_pager();
+        self.handle_export();
+        self.refresh_saved_searches();
     }
 
+    /// Enter in the entry searches right away
     pub fn handle_activate(&self) -> SignalHandlerId {
-        self.view
-            .search_entry
-            .connect_activate(|en| println!("{}", en.text()))
+        let controller = self.clone();
+        self.view.search_entry.connect_activate(move |entry| {
+            controller.view.suggestions.hide();
+            controller.submit(entry.text().as_str());
+        })
     }
 
     pub fn handle_click_search_button(&self) -> SignalHandlerId {
+        let controller = self.clone();
+        self.view.search_button.connect_clicked(move |_| {
+            let query = controller.view.search_entry.text();
+            controller.submit(query.as_str());
+        })
+    }
+
+    /// search as you type, the entry waits for a pause in the typing before emitting. The
+    /// query Enter just searched is not run a second time.
+    pub fn handle_search_changed(&self) {
+        let controller = self.clone();
+        self.view.handle_connect_search_changed(move |query| {
+            if *controller.pending_query.borrow() != query {
+                controller.search(query, false);
+            }
+        });
+    }
+
+    /// the same query typed again must search the new active index, not be taken for the
+    /// search that already ran
+    pub fn forget_pending_query(&self) {
+        self.pending_query.replace(String::new());
+    }
+
+    /// an explicit search, the query goes to the history once its hits are known
+    pub fn submit(&self, query: &str) {
+        self.search(query.to_string(), true);
+    }
+
+    /// sets the entry to `query` and searches it, for the suggestions and saved searches
+    fn rerun(&self, query: &str) {
+        self.pending_query.replace(query.to_string());
+        self.view.search_entry.set_text(query);
+        self.view.suggestions.hide();
+        self.submit(query);
+    }
+
+    /// runs `query` on a worker thread against the active index and streams the results
+    /// back into the view. `record` adds the query to the history.
+    pub fn search(&self, query: String, record: bool) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.pending_query.replace(query.clone());
+        let view = self.view.clone();
+        if query.trim().is_empty() {
+            view.clear_query_error();
+            view.clear_results();
+            return;
+        }
+        let Some((index, metadata)) = self
+            .model
+            .borrow()
+            .as_ref()
+            .map(|model| (model.data.clone(), model.metadata.clone()))
+        else {
+            debug!("no active index to search in");
+            return;
+        };
+        // the snippets of the first page are extracted with the search
+        let (limit, first_page) = {
+            let settings = self.settings.borrow();
+            (settings.result_limit, settings.page_size)
+        };
+        let (sender, receiver) = MainContext::channel::<SearchProgress>(Priority::DEFAULT);
+
+        let current = self.generation.clone();
+        let thread_query = query.clone();
+        thread::spawn(move || {
+            let is_stale = || current.load(Ordering::SeqCst) != generation;
+            run_search(
+                &index,
+                &metadata,
+                &thread_query,
+                limit,
+                first_page,
+                is_stale,
+                |progress| {
+                    let _ = sender.send(progress);
+                },
+            );
+        });
+
+        view.status_label.set_text("searching...");
+        let current = self.generation.clone();
+        let controller = self.clone();
+        receiver.attach(None, move |progress| {
+            if current.load(Ordering::SeqCst) != generation {
+                debug!(
+                    "dropping the results of {:?}, a newer search started",
+                    query
+                );
+                return ControlFlow::Break;
+            }
+            match progress {
+                SearchProgress::Ranked(ranking) => {
+                    debug!("{} results for {:?}", ranking.total, query);
+                    if record {
+                        controller.record(&query, ranking.total);
+                    }
+                    view.show_results(&query, ranking);
+                    ControlFlow::Continue
+                }
+                SearchProgress::Snippets(hits) => {
+                    view.set_snippets(0, hits);
+                    ControlFlow::Break
+                }
+                SearchProgress::Failed(e) => {
+                    debug!("invalid query {:?}: {}", query, e);
+                    view.status_label.set_text("");
+                    view.show_query_error(&e);
+                    ControlFlow::Break
+                }
+            }
+        });
+    }
+
+    fn record(&self, query: &str, hits: usize) {
+        let Some((id, name)) = self
+            .model
+            .borrow()
+            .as_ref()
+            .map(|model| (model.id.clone(), model.name.clone()))
+        else {
+            return;
+        };
+        let mut history = self.history.borrow_mut();
+        history.record(query, &id, &name, hits);
+        if let Err(e) = history.save() {
+            error!("could not save the search history: {}", e);
+        }
+    }
+
+    /// completes the entry with past and saved queries while the user types, and keeps the
+    /// pin button in sync with the text
+    fn handle_suggestions(&self) {
+        let controller = self.clone();
+        self.view.search_entry.connect_changed(move |entry| {
+            let text = entry.text();
+            let history = controller.history.borrow();
+            controller
+                .view
+                .pin_button
+                .set_active(history.is_pinned(text.as_str()));
+            if entry.has_focus() {
+                let suggestions = history.suggestions(text.as_str(), MAX_SUGGESTIONS);
+                controller.view.suggestions.show(&suggestions);
+            }
+        });
+        let suggestions = self.view.suggestions.clone();
         self.view
-            .search_button
-            .connect_clicked(move |button| println!("test:"))
+            .search_entry
+            .connect_stop_search(move |_| suggestions.hide());
+        let controller = self.clone();
+        self.view
+            .suggestions
+            .connect_pick(move |query| controller.rerun(query));
+    }
+
+    fn handle_pin(&self) {
+        let controller = self.clone();
+        self.view.pin_button.connect_clicked(move |button| {
+            let query = controller.view.search_entry.text();
+            let pinned = controller.history.borrow_mut().toggle_pin(query.as_str());
+            button.set_active(pinned);
+            if let Err(e) = controller.history.borrow().save() {
+                error!("could not save the search history: {}", e);
+            }
+            controller.refresh_saved_searches();
+        });
+    }
+
+    /// rebuilds the "Saved searches" section of the header bar menu
+    fn refresh_saved_searches(&self) {
+        let queries: Vec<String> = self
+            .history
+            .borrow()
+            .saved
+            .iter()
+            .map(|saved| saved.query.clone())
+            .collect();
+        let controller = self.clone();
+        self.headerbar
+            .set_saved_searches(&queries, Rc::new(move |query| controller.rerun(query)));
+    }
+
+    /// the page buttons move through the hits kept by the last search, the snippets of a page
+    /// are extracted the first time it is displayed
+    fn handle_pager(&self) {
+        let pager = &self.view.results.pager;
+        let controller = self.clone();
+        pager.previous_button.connect_clicked(move |_| {
+            let range = controller.view.previous_page();
+            controller.load_snippets(range);
+        });
+        let controller = self.clone();
+        pager.next_button.connect_clicked(move |_| {
+            let added = controller.view.next_page();
+            controller.load_snippets(added);
+        });
+        let controller = self.clone();
+        pager.load_more_button.connect_clicked(move |_| {
+            let added = controller.view.next_page();
+            controller.load_snippets(added);
+        });
+    }
+
+    /// extracts the snippets of the hits of `range` that have none yet on a worker thread
+    fn load_snippets(&self, range: Range<usize>) {
+        let start = range.start;
+        let hits = self.view.hits_in(range);
+        if hits.iter().all(|hit| !hit.snippets.is_empty()) {
+            return;
+        }
+        let generation = self.generation.load(Ordering::SeqCst);
+        let query = self.view.last_query.borrow().clone();
+        let (sender, receiver) = MainContext::channel::<Vec<SearchHit>>(Priority::DEFAULT);
+        thread::spawn(move || {
+            let mut hits = hits;
+            let count = hits.len();
+            attach_snippets(&mut hits, &query, count, &DocumentReader::default());
+            let _ = sender.send(hits);
+        });
+        let current = self.generation.clone();
+        let view = self.view.clone();
+        receiver.attach(None, move |hits| {
+            // a new search replaced the hits these snippets belong to
+            if current.load(Ordering::SeqCst) == generation {
+                view.set_snippets(start, hits);
+            }
+            ControlFlow::Break
+        });
+    }
+
+    /// "Export results…" saves every hit kept by the last search, the format follows the
+    /// extension of the chosen file
+    fn handle_export(&self) {
+        let controller = self.clone();
+        self.view.export_button.connect_clicked(move |button| {
+            let filters = ListStore::new::<FileFilter>();
+            for format in EXPORT_FORMATS {
+                let filter = FileFilter::new();
+                filter.set_name(Some(format.label()));
+                filter.add_suffix(format.extension());
+                filters.append(&filter);
+            }
+            let dialog = FileDialog::builder()
+                .title("Export results")
+                .modal(true)
+                .filters(&filters)
+                .initial_name("results.csv")
+                .build();
+            let window = button.root().and_downcast::<Window>();
+            let controller = controller.clone();
+            dialog.save(window.as_ref(), Cancellable::NONE, move |result| {
+                let file = match result {
+                    Ok(file) => file,
+                    Err(e) => {
+                        debug!("no export file chosen: {}", e);
+                        return;
+                    }
+                };
+                let Some(path) = file.path() else {
+                    controller
+                        .view
+                        .status_label
+                        .set_text("results can only be exported to a local file");
+                    return;
+                };
+                controller.export(&path);
+            });
+        });
+    }
+
+    /// writes the hits on a worker thread, the snippets of the hits never displayed are
+    /// extracted first
+    fn export(&self, path: &Path) {
+        let format = ExportFormat::from_path(path).unwrap_or(ExportFormat::Csv);
+        let mut hits = self.view.hits.borrow().clone();
+        let query = self.view.last_query.borrow().clone();
+        let path = path.to_path_buf();
+        self.view.status_label.set_text("exporting...");
+        self.view.export_button.set_sensitive(false);
+        let (sender, receiver) = MainContext::channel::<String>(Priority::DEFAULT);
+        thread::spawn(move || {
+            let count = hits.len();
+            attach_snippets(&mut hits, &query, count, &DocumentReader::default());
+            let result = File::create(&path)
+                .and_then(|file| write_results(&hits, format, &mut BufWriter::new(file)));
+            let status = match result {
+                Ok(()) => {
+                    info!("{} results exported to {:?}", count, path);
+                    format!("{} results exported to {}", count, path.to_string_lossy())
+                }
+                Err(e) => {
+                    error!("could not export the results to {:?}: {}", path, e);
+                    format!("export failed: {}", e)
+                }
+            };
+            let _ = sender.send(status);
+        });
+        let view = self.view.clone();
+        receiver.attach(None, move |status| {
+            view.status_label.set_text(&status);
+            view.export_button
+                .set_sensitive(!view.hits.borrow().is_empty());
+            ControlFlow::Break
+        });
     }
 }
@@ -0,0 +1,395 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use gtk::gio::{self, Cancellable, FileMonitor, FileMonitorEvent, FileMonitorFlags};
+use gtk::glib::{self, ControlFlow, MainContext, Priority, SourceId};
+use gtk::prelude::*;
+use log::{debug, error, info, warn};
+use search_engine::index::Index;
+
+use crate::models::catalog::IndexCatalog;
+use crate::models::index_job::{apply_changes, IndexChanges, IndexFilter, IndexJobHandle};
+use crate::models::index_model::StoredIndexModel;
+use crate::models::index_rules::IndexRules;
+use crate::models::metadata::IndexMetadata;
+use crate::views::main_view::MainView;
+
+/// changes are applied once the folder has been quiet for this long
+const FLUSH_DELAY: Duration = Duration::from_secs(3);
+/// the updated index is written to the catalog at most this often, and when watching stops
+const PERSIST_DELAY: Duration = Duration::from_secs(30);
+
+/// Keeps the active index up to date with its folder. gio monitors are not recursive, so
+/// every folder under the root gets its own; the files they report are queued and applied
+/// in the background once the events stop. The updated index replaces the active one right
+/// away but is only written to the catalog from time to time.
+#[derive(Clone)]
+pub struct WatchController {
+    main_view: MainView,
+    /// the root folder watched, None when watching is off or the index has no known folder
+    root: Rc<RefCell<Option<PathBuf>>>,
+    /// the rules the index was built with, the watcher leaves out the same files
+    rules: Rc<RefCell<IndexRules>>,
+    monitors: Rc<RefCell<HashMap<PathBuf, FileMonitor>>>,
+    pending: Rc<RefCell<BTreeSet<PathBuf>>>,
+    flush_source: Rc<RefCell<Option<SourceId>>>,
+    /// true while a worker applies the changes, new events wait for the next flush
+    flushing: Rc<Cell<bool>>,
+    /// the indexing job of the main controller, nothing is applied or stored while it runs
+    job: Rc<RefCell<Option<IndexJobHandle>>>,
+    /// the index updated by the watcher and not written to the catalog yet, with its folder
+    /// and rules
+    unsaved: Rc<RefCell<Option<(PathBuf, IndexRules, StoredIndexModel)>>>,
+    persist_source: Rc<RefCell<Option<SourceId>>>,
+    /// true while a worker writes the index to the catalog
+    persisting: Rc<Cell<bool>>,
+}
+
+impl WatchController {
+    pub fn new(main_view: &MainView, job: &Rc<RefCell<Option<IndexJobHandle>>>) -> Self {
+        Self {
+            main_view: main_view.clone(),
+            root: Rc::new(RefCell::new(None)),
+            rules: Rc::new(RefCell::new(IndexRules::default())),
+            monitors: Rc::new(RefCell::new(HashMap::new())),
+            pending: Rc::new(RefCell::new(BTreeSet::new())),
+            flush_source: Rc::new(RefCell::new(None)),
+            flushing: Rc::new(Cell::new(false)),
+            job: job.clone(),
+            unsaved: Rc::new(RefCell::new(None)),
+            persist_source: Rc::new(RefCell::new(None)),
+            persisting: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn setup(&self) {
+        let controller = self.clone();
+        self.main_view
+            .connect_active_index_changed(move || controller.follow_active_index());
+        let controller = self.clone();
+        self.main_view
+            .watch_switch
+            // only for this session, `watch_changes` in the config file sets the state at
+            // startup
+            .connect_active_notify(move |_| controller.follow_active_index());
+        self.follow_active_index();
+    }
+
+    /// the folder of the active index and its rules, when it comes from the catalog
+    fn active_folder(&self) -> Option<(PathBuf, IndexRules)> {
+        let id = self.main_view.model.borrow().as_ref()?.id.clone();
+        let settings = self.main_view.settings.borrow();
+        IndexCatalog::load()
+            .find(&id)
+            .map(|entry| (entry.root.clone(), entry.rules(&settings)))
+    }
+    fn filter(&self) -> IndexFilter {
+        IndexFilter::new(&self.rules.borrow())
+    }
+
+    /// watches the folder of the active index, or stops watching. An index updated by the
+    /// watcher itself keeps the same folder, rules and monitors.
+    fn follow_active_index(&self) {
+        let folder = if self.main_view.watch_switch.is_active() {
+            self.active_folder()
+        } else {
+            None
+        };
+        let unchanged = match &folder {
+            Some((root, rules)) => {
+                self.root.borrow().as_ref() == Some(root) && *self.rules.borrow() == *rules
+            }
+            None => self.root.borrow().is_none(),
+        };
+        if unchanged {
+            self.refresh_status();
+            return;
+        }
+        self.stop();
+        if let Some((root, rules)) = folder {
+            info!("watching {:?} for changes", root);
+            self.rules.replace(rules);
+            self.root.replace(Some(root.clone()));
+            self.watch_tree(&root, &root, &self.filter());
+        }
+        self.refresh_status();
+    }
+
+    fn stop(&self) {
+        self.persist(false);
+        for (_, monitor) in self.monitors.borrow_mut().drain() {
+            monitor.cancel();
+        }
+        if let Some(source) = self.flush_source.take() {
+            source.remove();
+        }
+        self.pending.borrow_mut().clear();
+        self.root.replace(None);
+    }
+
+    /// monitors `dir` and every folder under it that can contain indexed files
+    fn watch_tree(&self, root: &Path, dir: &Path, filter: &IndexFilter) {
+        if filter.skips_dir(dir)
+            || filter.is_ignored(root, dir, true)
+            || self.monitors.borrow().contains_key(dir)
+        {
+            return;
+        }
+        let file = gio::File::for_path(dir);
+        match file.monitor_directory(FileMonitorFlags::WATCH_MOVES, Cancellable::NONE) {
+            Ok(monitor) => {
+                let controller = self.clone();
+                monitor.connect_changed(move |_, file, other_file, event| {
+                    controller.handle_event(file, other_file, event);
+                });
+                self.monitors
+                    .borrow_mut()
+                    .insert(dir.to_path_buf(), monitor);
+            }
+            Err(e) => warn!("{:?} can't be watched: {}", dir, e),
+        }
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                self.watch_tree(root, &entry.path(), filter);
+            }
+        }
+    }
+
+    fn handle_event(
+        &self,
+        file: &gio::File,
+        other_file: Option<&gio::File>,
+        event: FileMonitorEvent,
+    ) {
+        let paths: Vec<PathBuf> = match event {
+            FileMonitorEvent::Created
+            | FileMonitorEvent::ChangesDoneHint
+            | FileMonitorEvent::Deleted
+            | FileMonitorEvent::MovedIn
+            | FileMonitorEvent::MovedOut => file.path().into_iter().collect(),
+            FileMonitorEvent::Renamed => file
+                .path()
+                .into_iter()
+                .chain(other_file.and_then(|other| other.path()))
+                .collect(),
+            _ => return,
+        };
+        let Some(root) = self.root.borrow().clone() else {
+            return;
+        };
+        for path in paths {
+            if path.is_dir() {
+                // a new folder: watch it and queue what it already contains
+                self.watch_tree(&root, &path, &self.filter());
+                self.queue_files_under(&path);
+            } else {
+                if !path.exists() {
+                    // a deleted folder loses its monitor, its files are removed when applied
+                    if let Some(monitor) = self.monitors.borrow_mut().remove(&path) {
+                        monitor.cancel();
+                    }
+                }
+                debug!("queued change of {:?}", path);
+                self.pending.borrow_mut().insert(path);
+            }
+        }
+        self.schedule_flush();
+    }
+
+    fn queue_files_under(&self, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                self.queue_files_under(&path);
+            } else {
+                self.pending.borrow_mut().insert(path);
+            }
+        }
+    }
+
+    /// (re)starts the countdown to the next flush, so a burst of events is applied at once
+    fn schedule_flush(&self) {
+        if let Some(source) = self.flush_source.take() {
+            source.remove();
+        }
+        let controller = self.clone();
+        let source = glib::timeout_add_local_once(FLUSH_DELAY, move || {
+            controller.flush_source.replace(None);
+            controller.flush();
+        });
+        self.flush_source.replace(Some(source));
+        self.refresh_status();
+    }
+
+    /// applies the queued changes to a copy of the active index on a worker thread and makes
+    /// it the active index, unless the active index was replaced meanwhile
+    fn flush(&self) {
+        if self.pending.borrow().is_empty() {
+            return;
+        }
+        if self.flushing.get() || self.job_running() {
+            // an indexing job walks the folder again, what it misses is applied after it
+            self.schedule_flush();
+            return;
+        }
+        let (Some(root), Some(snapshot)) = (
+            self.root.borrow().clone(),
+            self.main_view.model.borrow().clone(),
+        ) else {
+            return;
+        };
+        let paths: Vec<PathBuf> = self.pending.take().into_iter().collect();
+        let rules = self.rules.borrow().clone();
+        self.flushing.set(true);
+        self.refresh_status();
+
+        let (sender, receiver) =
+            MainContext::channel::<(Index, IndexMetadata, IndexChanges)>(Priority::DEFAULT);
+        let (worker_snapshot, worker_paths) = (snapshot.clone(), paths.clone());
+        let filter = IndexFilter::new(&rules);
+        let worker_root = root.clone();
+        thread::spawn(move || {
+            let _ = sender.send(apply_changes(
+                &worker_snapshot,
+                &worker_root,
+                &worker_paths,
+                &filter,
+            ));
+        });
+
+        let controller = self.clone();
+        receiver.attach(None, move |(index, metadata, changes)| {
+            controller.flushing.set(false);
+            let active = controller.main_view.model.borrow().clone();
+            match active {
+                // the changes were applied to the index still in use
+                Some(active) if Arc::ptr_eq(&active.data, &snapshot.data) => {
+                    info!("watched changes applied: {}", changes);
+                    let model = StoredIndexModel {
+                        data: Arc::new(index),
+                        metadata: Arc::new(metadata),
+                        ..active
+                    };
+                    controller
+                        .unsaved
+                        .replace(Some((root.clone(), rules.clone(), model.clone())));
+                    controller.main_view.set_active_index(model);
+                    controller.schedule_persist();
+                }
+                // the index was rebuilt meanwhile, the changes are applied to the new one
+                Some(active) if active.id == snapshot.id => {
+                    debug!("the index changed while applying the watched changes, retrying");
+                    controller
+                        .pending
+                        .borrow_mut()
+                        .extend(paths.iter().cloned());
+                    controller.schedule_flush();
+                }
+                _ => debug!("another index is active, the watched changes are dropped"),
+            }
+            controller.refresh_status();
+            ControlFlow::Break
+        });
+    }
+
+    fn job_running(&self) -> bool {
+        self.job.borrow().is_some()
+    }
+
+    fn schedule_persist(&self) {
+        if self.persist_source.borrow().is_some() {
+            return;
+        }
+        let controller = self.clone();
+        let source = glib::timeout_add_local_once(PERSIST_DELAY, move || {
+            controller.persist_source.replace(None);
+            controller.persist(false);
+        });
+        self.persist_source.replace(Some(source));
+    }
+
+    /// writes the index updated by the watcher to the catalog, in the background or, when
+    /// `wait` is true, before returning. An index rebuilt since then is not overwritten.
+    fn persist(&self, wait: bool) {
+        if let Some(source) = self.persist_source.take() {
+            source.remove();
+        }
+        if !wait && (self.persisting.get() || self.job_running()) {
+            if self.unsaved.borrow().is_some() {
+                self.schedule_persist();
+            }
+            return;
+        }
+        let Some((root, rules, model)) = self.unsaved.take() else {
+            return;
+        };
+        let replaced = self
+            .main_view
+            .model
+            .borrow()
+            .as_ref()
+            .is_some_and(|active| active.id == model.id && !Arc::ptr_eq(&active.data, &model.data));
+        if replaced {
+            debug!(
+                "the index of {:?} was rebuilt, the watched changes are not stored",
+                root
+            );
+            return;
+        }
+        if wait {
+            if let Err(e) = IndexCatalog::store(&root, &model.data, &model.metadata, &rules) {
+                error!("the watched changes could not be stored: {}", e);
+            }
+            return;
+        }
+        self.persisting.set(true);
+        let (sender, receiver) = MainContext::channel::<Result<(), String>>(Priority::DEFAULT);
+        thread::spawn(move || {
+            let result = IndexCatalog::store(&root, &model.data, &model.metadata, &rules)
+                .map(|entry| info!("index of {:?} stored as {}", root, entry.id))
+                .map_err(|e| e.to_string());
+            let _ = sender.send(result);
+        });
+        let controller = self.clone();
+        receiver.attach(None, move |result| {
+            controller.persisting.set(false);
+            if let Err(e) = result {
+                error!("the watched changes could not be stored: {}", e);
+            }
+            ControlFlow::Break
+        });
+    }
+
+    /// stores what was not written yet before the application exits
+    pub fn shutdown(&self) {
+        self.persist(true);
+    }
+
+    fn refresh_status(&self) {
+        let pending = self.pending.borrow().len();
+        let text = if self.root.borrow().is_none() {
+            String::from("not watching")
+        } else if self.flushing.get() {
+            String::from("updating the index...")
+        } else if pending == 0 {
+            String::from("index up to date")
+        } else if pending == 1 {
+            String::from("1 pending change")
+        } else {
+            format!("{} pending changes", pending)
+        };
+        self.main_view.watch_label.set_text(&text);
+    }
+}
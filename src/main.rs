@@ -1,11 +1,13 @@
 extern crate env_logger;
 extern crate log;
 extern crate search_engine;
+mod cli;
 mod config;
 mod controllers;
 mod custom_button;
 mod models;
 mod types;
+mod utils;
 mod views;
 mod widgets;
 
@@ -13,7 +15,7 @@ use log::{debug, info};
 use search_engine::types::WrapInRcRefCell;
 use std::{cell::RefCell, rc::Rc};
 
-use config::{set_log_level, APP_ID};
+use config::{init_logger, Settings, APP_ID};
 use views::main_view::MainView;
 //Beware that we use the command : cargo add gtk4 --rename gtk --features v4_12_3 to add gtk4 and
 //to use it like this as gkt. We could have also do:
@@ -21,19 +23,33 @@ use views::main_view::MainView;
 use gtk::{glib, prelude::*, Application};
 
 use controllers::main_controller::MainController;
+use models::archive::remove_extracted;
+use models::catalog::IndexCatalog;
 
 ///the main() function is set to the bare minimum. The App uses MVC architecture so only the MainView struct is required to build the entire
-///aplication and start its logics with the help of different views and controllers
+///aplication and start its logics with the help of different views and controllers.
+///When a subcommand is given (see cli.rs) the app runs headless and never initializes gtk,
+///any other argument goes to the gtk application.
 
 fn main() -> glib::ExitCode {
-    set_log_level("debug");
+    // the config file is read once, the views and controllers share what was loaded
+    let settings = Settings::load();
+    init_logger(&settings.log_level);
+    IndexCatalog::set_storage_dir(&settings.storage_dir);
     info!(":: Application {} started ::", APP_ID);
     debug!(":: DEBUG MOD ON ::");
+    // subcommands run headless, gtk is only started when none is given
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(exit_code) = cli::run(args.get(1..).unwrap_or_default(), &settings) {
+        return exit_code;
+    }
     let _ = gtk::init();
     let app = Application::builder().application_id(APP_ID).build();
     // Set keyboard accelerator to trigger "win.close".
     app.set_accels_for_action("win.close", &["<Ctrl>W"]);
-    let main_window = MainView::new().wrap_and_clone();
+    // the archive entries extracted to be opened live as long as the application
+    app.connect_shutdown(|_| remove_extracted());
+    let main_window = MainView::new(&Rc::new(RefCell::new(settings))).wrap_and_clone();
     let main_controller = MainController::new(&main_window.borrow_mut());
 
     app.connect_activate(move |app| {
@@ -43,10 +59,17 @@ fn main() -> glib::ExitCode {
             &borrowed_main_view.folder_label,
             &borrowed_main_view.directory,
         );
-        main_controller.handle_search_clicked(&borrowed_main_view.input_view.search_button);
+        main_controller.handle_search();
+        main_controller.handle_job_controls();
+        main_controller.handle_result_actions();
+        main_controller.handle_watcher(app);
+        main_controller.handle_new_index_clicked();
+        main_controller.handle_catalog_clicked();
+        main_controller.handle_open_index_clicked();
+        main_controller.handle_settings_clicked();
 
         borrowed_main_view.build_ui(&app)
     });
 
-    app.run()
+    app.run_with_args(&args)
 }
@@ -0,0 +1,463 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process,
+};
+
+use flate2::read::GzDecoder;
+use log::warn;
+use search_engine::utils::generate_uid;
+
+/// separates the path of an archive from the name of an entry inside it, as in
+/// `bundle.zip!/docs/readme.md`
+pub const ENTRY_SEPARATOR: &str = "!/";
+/// entries are read up to this many bytes, whatever size their header claims, larger ones
+/// are skipped
+pub const MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// the kind of archive `path` is, from its extension
+    pub fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// the path under which the entry `name` of `archive` is indexed
+pub fn entry_path(archive: &Path, name: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}{}{}",
+        archive.to_string_lossy(),
+        ENTRY_SEPARATOR,
+        name
+    ))
+}
+
+/// the archive and the entry name of a path made by entry_path(), None for a file on disk
+pub fn split_entry_path(path: &Path) -> Option<(PathBuf, String)> {
+    let path = path.to_string_lossy();
+    let mut search_from = 0;
+    while let Some(found) = path[search_from..].find(ENTRY_SEPARATOR) {
+        let split = search_from + found;
+        let archive = Path::new(&path[..split]);
+        if ArchiveKind::of(archive).is_some() {
+            let name = path[split + ENTRY_SEPARATOR.len()..].to_string();
+            return Some((archive.to_path_buf(), name));
+        }
+        search_from = split + ENTRY_SEPARATOR.len();
+    }
+    None
+}
+
+/// the file on disk holding `path`: the archive of an entry, or the path itself
+pub fn file_on_disk(path: &Path) -> PathBuf {
+    split_entry_path(path)
+        .map(|(archive, _)| archive)
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// whether `key` is `path` or lies under it, in a folder or in an archive
+pub fn is_under(key: &Path, path: &Path) -> bool {
+    key.starts_with(path)
+        || key.to_string_lossy().starts_with(&format!(
+            "{}{}",
+            path.to_string_lossy(),
+            ENTRY_SEPARATOR
+        ))
+}
+
+/// calls `visit` with the name and content of every file of `archive` for which
+/// `wanted(name, size)` is true. Archives inside the archive are not opened, entries larger
+/// than MAX_ENTRY_SIZE are skipped.
+pub fn visit_entries<W, V>(archive: &Path, wanted: W, mut visit: V) -> Result<(), String>
+where
+    W: Fn(&str, u64) -> bool,
+    V: FnMut(&str, Vec<u8>),
+{
+    let kind = ArchiveKind::of(archive)
+        .ok_or_else(|| format!("{} is not an archive", archive.to_string_lossy()))?;
+    let file = fs::File::open(archive).map_err(|e| e.to_string())?;
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+                // enclosed_name() leaves out names escaping the archive, like ../../etc
+                let Some(name) = entry
+                    .enclosed_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                else {
+                    continue;
+                };
+                if !entry.is_file() || !wanted(&name, entry.size()) {
+                    continue;
+                }
+                if let Some(content) = read_limited(&mut entry, &name, MAX_ENTRY_SIZE)? {
+                    visit(&name, content);
+                }
+            }
+            Ok(())
+        }
+        ArchiveKind::Tar => visit_tar(tar::Archive::new(file), wanted, visit),
+        ArchiveKind::TarGz => visit_tar(tar::Archive::new(GzDecoder::new(file)), wanted, visit),
+    }
+}
+
+fn visit_tar<R, W, V>(mut archive: tar::Archive<R>, wanted: W, mut visit: V) -> Result<(), String>
+where
+    R: Read,
+    W: Fn(&str, u64) -> bool,
+    V: FnMut(&str, Vec<u8>),
+{
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .to_string();
+        if !wanted(&name, entry.size()) {
+            continue;
+        }
+        if let Some(content) = read_limited(&mut entry, &name, MAX_ENTRY_SIZE)? {
+            visit(&name, content);
+        }
+    }
+    Ok(())
+}
+
+/// the content of an entry, None when it is larger than `limit` bytes. The size in the
+/// header is not trusted, a forged one could make us allocate anything.
+fn read_limited<R: Read>(entry: &mut R, name: &str, limit: u64) -> Result<Option<Vec<u8>>, String> {
+    let mut content = Vec::new();
+    entry
+        .take(limit + 1)
+        .read_to_end(&mut content)
+        .map_err(|e| format!("{}: {}", name, e))?;
+    if content.len() as u64 > limit {
+        warn!("{} is larger than {} bytes, skipped", name, limit);
+        return Ok(None);
+    }
+    Ok(Some(content))
+}
+
+/// the content of the entry `name` of `archive`. A zip archive is looked up by name, a tar
+/// archive has no index and is read up to the entry.
+pub fn read_entry(archive: &Path, name: &str) -> Result<Vec<u8>, String> {
+    let missing = || format!("{} has no entry {}", archive.to_string_lossy(), name);
+    if ArchiveKind::of(archive) == Some(ArchiveKind::Zip) {
+        let file = fs::File::open(archive).map_err(|e| e.to_string())?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+        let mut entry = zip.by_name(name).map_err(|_| missing())?;
+        return read_limited(&mut entry, name, MAX_ENTRY_SIZE)?.ok_or_else(missing);
+    }
+    let mut found = None;
+    visit_entries(
+        archive,
+        |entry, _| entry == name,
+        |_, content| found = Some(content),
+    )?;
+    found.ok_or_else(missing)
+}
+
+/// The entries of the tar archives read while running one search. Reading one entry of a
+/// tar archive means reading the archive up to it, so the entries the search announced with
+/// expect() are kept on the way. The others are read and dropped.
+#[derive(Default)]
+pub struct EntryCache {
+    /// the entry names that will be read, by archive
+    wanted: HashMap<PathBuf, HashSet<String>>,
+    archives: HashMap<PathBuf, HashMap<String, Vec<u8>>>,
+}
+
+impl EntryCache {
+    /// announces the documents about to be read, the entries kept for earlier ones and not
+    /// among `paths` are dropped
+    pub fn expect<'a, I>(&mut self, paths: I)
+    where
+        I: IntoIterator<Item = &'a Path>,
+    {
+        let mut wanted: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+        for (archive, name) in paths.into_iter().filter_map(split_entry_path) {
+            if matches!(
+                ArchiveKind::of(&archive),
+                Some(ArchiveKind::Tar) | Some(ArchiveKind::TarGz)
+            ) {
+                wanted.entry(archive).or_default().insert(name);
+            }
+        }
+        self.archives.retain(|archive, entries| {
+            let names = wanted.get(archive);
+            entries.retain(|name, _| names.is_some_and(|names| names.contains(name)));
+            !entries.is_empty()
+        });
+        self.wanted = wanted;
+    }
+    pub fn read(&mut self, archive: &Path, name: &str) -> Result<Vec<u8>, String> {
+        if matches!(ArchiveKind::of(archive), Some(ArchiveKind::Zip) | None) {
+            return read_entry(archive, name);
+        }
+        if let Some(content) = self
+            .archives
+            .get(archive)
+            .and_then(|entries| entries.get(name))
+        {
+            return Ok(content.clone());
+        }
+        let cached = self.archives.entry(archive.to_path_buf()).or_default();
+        let missing: HashSet<&str> = self
+            .wanted
+            .get(archive)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|entry| !cached.contains_key(*entry))
+            .collect();
+        let mut found = None;
+        visit_entries(
+            archive,
+            |entry, _| entry == name || missing.contains(&entry),
+            |entry, content| {
+                if missing.contains(&entry) {
+                    if entry == name {
+                        found = Some(content.clone());
+                    }
+                    cached.insert(entry.to_string(), content);
+                } else if entry == name {
+                    found = Some(content);
+                }
+            },
+        )?;
+        if cached.is_empty() {
+            self.archives.remove(archive);
+        }
+        found.ok_or_else(|| format!("{} has no entry {}", archive.to_string_lossy(), name))
+    }
+}
+
+/// writes `content` to a temporary file named like the entry `name`, so its type can be
+/// guessed, and calls `f` with it before removing it
+pub fn with_temp_copy<T, F>(name: &str, content: &[u8], f: F) -> io::Result<T>
+where
+    F: FnOnce(&Path) -> T,
+{
+    let dir = env::temp_dir().join(format!("rustindexer-{}", generate_uid()));
+    fs::create_dir_all(&dir)?;
+    let copy = dir.join(Path::new(name).file_name().unwrap_or("entry".as_ref()));
+    let result = fs::write(&copy, content).map(|_| f(&copy));
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        warn!("{:?} could not be removed: {}", dir, e);
+    }
+    result
+}
+
+/// the folder the entries opened during this session are extracted to
+fn extracted_dir() -> PathBuf {
+    env::temp_dir().join(format!("rustindexer-open-{}", process::id()))
+}
+
+/// extracts the entry behind `path` to a temporary file for an application to open it. The
+/// file stays until remove_extracted() is called when the application exits.
+pub fn extract_entry(path: &Path) -> Result<PathBuf, String> {
+    let (archive, name) = split_entry_path(path)
+        .ok_or_else(|| format!("{} is not in an archive", path.to_string_lossy()))?;
+    let content = read_entry(&archive, &name)?;
+    // entries of different archives may share a name
+    let dir = extracted_dir().join(generate_uid());
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let copy = dir.join(Path::new(&name).file_name().unwrap_or("entry".as_ref()));
+    fs::write(&copy, content).map_err(|e| e.to_string())?;
+    Ok(copy)
+}
+
+/// removes the entries extracted by extract_entry() during this session
+pub fn remove_extracted() {
+    let dir = extracted_dir();
+    if dir.exists() {
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            warn!("{:?} could not be removed: {}", dir, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a folder of its own under the temporary directory, removed by the caller
+    fn test_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("rustindexer-test-{}", generate_uid()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_tar(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut builder = tar::Builder::new(fs::File::create(path).unwrap());
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *content).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    fn cached_names(cache: &EntryCache, archive: &Path) -> Vec<String> {
+        let mut names: Vec<String> = cache
+            .archives
+            .get(archive)
+            .map(|entries| entries.keys().cloned().collect())
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn entry_cache_keeps_only_the_expected_tar_entries() {
+        let dir = test_dir();
+        let archive = dir.join("docs.tar");
+        write_tar(
+            &archive,
+            &[
+                ("a.txt", b"alpha"),
+                ("b.txt", b"beta"),
+                ("c.bin", &[0; 4096]),
+            ],
+        );
+        let mut cache = EntryCache::default();
+        let expected = [entry_path(&archive, "a.txt")];
+        cache.expect(expected.iter().map(PathBuf::as_path));
+
+        // reading an entry that was not announced keeps the announced one on the way
+        assert_eq!(cache.read(&archive, "b.txt").unwrap(), b"beta");
+        assert_eq!(cached_names(&cache, &archive), ["a.txt"]);
+        assert_eq!(cache.read(&archive, "a.txt").unwrap(), b"alpha");
+        assert!(cache.read(&archive, "missing.txt").is_err());
+
+        // the next documents replace the announced ones
+        cache.expect(std::iter::empty());
+        assert!(cache.archives.is_empty());
+        assert_eq!(cache.read(&archive, "a.txt").unwrap(), b"alpha");
+        assert!(cache.archives.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn archive_kind_comes_from_the_extension() {
+        assert_eq!(ArchiveKind::of(Path::new("a.ZIP")), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::of(Path::new("a.tar")), Some(ArchiveKind::Tar));
+        assert_eq!(
+            ArchiveKind::of(Path::new("a.tar.gz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            ArchiveKind::of(Path::new("a.tgz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(ArchiveKind::of(Path::new("a.gz")), None);
+        assert_eq!(ArchiveKind::of(Path::new("zip")), None);
+    }
+
+    #[test]
+    fn entry_paths_split_at_the_first_archive() {
+        let split = |path: &str| {
+            split_entry_path(Path::new(path))
+                .map(|(archive, name)| (archive.to_string_lossy().to_string(), name))
+        };
+        let archive = Path::new("/docs/bundle.zip");
+        let path = entry_path(archive, "notes/readme.md");
+        assert_eq!(path, Path::new("/docs/bundle.zip!/notes/readme.md"));
+        assert_eq!(
+            split_entry_path(&path),
+            Some((archive.to_path_buf(), String::from("notes/readme.md")))
+        );
+        // a `!/` in a folder or entry name is not a separator
+        assert_eq!(
+            split("/docs/wow!/bundle.tar!/hey!/a.txt"),
+            Some((
+                String::from("/docs/wow!/bundle.tar"),
+                String::from("hey!/a.txt")
+            ))
+        );
+        // archives inside an archive are not opened, their entries belong to the outer one
+        assert_eq!(
+            split("/docs/a.zip!/b.tgz!/c.txt"),
+            Some((String::from("/docs/a.zip"), String::from("b.tgz!/c.txt")))
+        );
+        for path in ["/docs/a.zip", "/docs/wow!/a.txt", "/docs/a.txt!/b.txt", ""] {
+            assert_eq!(split(path), None, "{}", path);
+        }
+        assert_eq!(file_on_disk(&path), archive);
+        assert_eq!(file_on_disk(archive), archive);
+    }
+
+    #[test]
+    fn is_under_follows_folders_and_archives() {
+        let archive = Path::new("/docs/a.zip");
+        assert!(is_under(archive, archive));
+        assert!(is_under(&entry_path(archive, "b.txt"), archive));
+        assert!(is_under(&entry_path(archive, "b.txt"), Path::new("/docs")));
+        assert!(is_under(
+            Path::new("/docs/sub/b.txt"),
+            Path::new("/docs/sub")
+        ));
+        assert!(!is_under(Path::new("/docs/a.zip2!/b.txt"), archive));
+        assert!(!is_under(
+            Path::new("/docs/subfolder/b.txt"),
+            Path::new("/docs/sub")
+        ));
+    }
+
+    #[test]
+    fn entries_over_the_limit_are_skipped() {
+        let content = [7; 10];
+        let read = |limit| read_limited(&mut &content[..], "a.bin", limit).unwrap();
+        assert_eq!(read(10), Some(content.to_vec()));
+        assert_eq!(read(9), None);
+        assert_eq!(read(0), None);
+        // the reader is never read past the limit, whatever its size
+        let mut endless = io::repeat(1);
+        assert_eq!(read_limited(&mut endless, "endless", 100).unwrap(), None);
+    }
+
+    #[test]
+    fn entries_are_read_by_name() {
+        let dir = test_dir();
+        let archive = dir.join("docs.tar");
+        write_tar(&archive, &[("./a.txt", b"alpha"), ("sub/b.txt", b"beta")]);
+        assert_eq!(read_entry(&archive, "a.txt").unwrap(), b"alpha");
+        assert_eq!(read_entry(&archive, "sub/b.txt").unwrap(), b"beta");
+        assert!(read_entry(&archive, "c.txt").is_err());
+        let mut names = Vec::new();
+        visit_entries(
+            &archive,
+            |name, _| name != "a.txt",
+            |name, _| names.push(name.to_string()),
+        )
+        .unwrap();
+        assert_eq!(names, ["sub/b.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
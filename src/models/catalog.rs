@@ -0,0 +1,285 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, error};
+use search_engine::{index::Index, utils::generate_uid};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Settings;
+use crate::models::index_rules::IndexRules;
+use crate::models::metadata::IndexMetadata;
+use crate::types::StandardResult;
+use crate::utils::write_json_atomic;
+
+/// held while the catalog is read, modified and written back, so the indexing and watching
+/// threads don't overwrite each other's changes or a rename made in the meantime
+static CATALOG_LOCK: Mutex<()> = Mutex::new(());
+/// the storage directory of the settings, readable from the worker threads. Set at startup
+/// and when the settings are saved, read from the config file if it was not.
+static STORAGE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// metadata describing one stored index, the index itself lives in `<id>.json` next to the
+/// catalog file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub id: String,
+    pub name: String,
+    pub root: PathBuf,
+    /// unix timestamps in seconds
+    pub created: u64,
+    pub updated: u64,
+    pub document_count: usize,
+    pub size_on_disk: u64,
+    /// what was indexed under `root`, None for indexes built before the rules were recorded
+    #[serde(default)]
+    pub rules: Option<IndexRules>,
+}
+
+impl CatalogEntry {
+    /// the rules of the index, or the ones of `settings` when none were recorded
+    pub fn rules(&self, settings: &Settings) -> IndexRules {
+        self.rules
+            .clone()
+            .unwrap_or_else(|| IndexRules::from_settings(settings))
+    }
+}
+
+/// The list of every index built by the application, one per root folder. It is stored as
+/// json in the storage directory of the settings (the XDG data directory by default).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IndexCatalog {
+    pub entries: Vec<CatalogEntry>,
+}
+
+impl IndexCatalog {
+    pub fn storage_dir() -> PathBuf {
+        STORAGE_DIR
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get_or_insert_with(|| Settings::load().storage_dir)
+            .clone()
+    }
+    pub fn set_storage_dir(dir: &Path) {
+        STORAGE_DIR
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .replace(dir.to_path_buf());
+    }
+    fn catalog_path() -> PathBuf {
+        Self::storage_dir().join("catalog.json")
+    }
+    pub fn index_path(id: &str) -> PathBuf {
+        Self::storage_dir().join(format!("{}.json", id))
+    }
+    /// loads the catalog, a missing or unreadable catalog is treated as an empty one
+    pub fn load() -> Self {
+        let path = Self::catalog_path();
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                error!("the catalog {:?} could not be parsed: {}", path, e);
+                Self::default()
+            }),
+            Err(e) => {
+                debug!("no catalog loaded from {:?}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+    fn save(&self) -> StandardResult {
+        write_json_atomic(self, &Self::catalog_path())
+    }
+    /// applies `change` to the catalog as currently stored and saves it. Every write goes
+    /// through here, one at a time.
+    fn update<T, F>(change: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Box<dyn std::error::Error>>,
+    {
+        // a thread that panicked while holding the lock left no partial write behind, the
+        // catalog is written atomically
+        let _guard = CATALOG_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut catalog = Self::load();
+        let result = change(&mut catalog)?;
+        catalog.save()?;
+        Ok(result)
+    }
+    pub fn find(&self, id: &str) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+    pub fn find_by_root(&self, root: &Path) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|entry| entry.root == root)
+    }
+    /// the most recently updated index, the one loaded at startup
+    pub fn latest(&self) -> Option<&CatalogEntry> {
+        self.entries.iter().max_by_key(|entry| entry.updated)
+    }
+    /// writes `index` and its metadata to disk and records it in the catalog with the `rules`
+    /// it was built with. An index already built for the same root folder is replaced, so
+    /// rebuilding a folder keeps its id and name.
+    pub fn store(
+        root: &Path,
+        index: &Index,
+        metadata: &IndexMetadata,
+        rules: &IndexRules,
+    ) -> Result<CatalogEntry, Box<dyn std::error::Error>> {
+        Self::update(|catalog| {
+            let now = unix_now();
+            let position = catalog.entries.iter().position(|entry| entry.root == root);
+            let mut entry = match position {
+                Some(position) => catalog.entries.remove(position),
+                None => CatalogEntry {
+                    id: generate_uid(),
+                    name: root
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_else(|| root.to_string_lossy().to_string()),
+                    root: root.to_path_buf(),
+                    created: now,
+                    updated: now,
+                    document_count: 0,
+                    size_on_disk: 0,
+                    rules: None,
+                },
+            };
+            let path = Self::index_path(&entry.id);
+            write_json_atomic(index, &path)?;
+            metadata.save(&path)?;
+            entry.updated = now;
+            entry.rules = Some(rules.clone());
+            entry.document_count = index.index.len();
+            entry.size_on_disk = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+            catalog.entries.push(entry.clone());
+            Ok(entry)
+        })
+    }
+    pub fn rename(id: &str, name: &str) -> StandardResult {
+        Self::update(|catalog| {
+            if let Some(entry) = catalog.entries.iter_mut().find(|entry| entry.id == id) {
+                entry.name = name.to_string();
+            }
+            Ok(())
+        })
+    }
+    /// removes the entry and deletes its index and metadata files
+    pub fn remove(id: &str) -> StandardResult {
+        Self::update(|catalog| {
+            catalog.entries.retain(|entry| entry.id != id);
+            let path = Self::index_path(id);
+            let metadata_path = IndexMetadata::sidecar_path(&path);
+            if metadata_path.exists() {
+                fs::remove_file(metadata_path)?;
+            }
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env};
+
+    use super::*;
+
+    /// an index of `count` documents under `root`
+    fn index(root: &Path, count: usize) -> Index {
+        let mut index = Index::new(Vec::new());
+        for i in 0..count {
+            let path = root.join(format!("{}.txt", i));
+            index.index.insert(path, HashMap::new());
+        }
+        index
+    }
+
+    // STORAGE_DIR is shared by the whole process, so the catalog is exercised by a single
+    // test
+    #[test]
+    fn catalog_keeps_one_entry_per_root() {
+        let dir = env::temp_dir().join(format!("rustindexer-test-{}", generate_uid()));
+        IndexCatalog::set_storage_dir(&dir);
+        assert!(IndexCatalog::load().entries.is_empty());
+        let (docs, notes) = (Path::new("/home/user/docs"), Path::new("/home/user/notes"));
+        let rules = IndexRules::default();
+        let metadata = IndexMetadata::default();
+
+        let stored = IndexCatalog::store(docs, &index(docs, 1), &metadata, &rules).unwrap();
+        assert_eq!(stored.name, "docs");
+        assert_eq!(stored.document_count, 1);
+        assert_eq!(stored.rules, Some(rules.clone()));
+        let index_path = IndexCatalog::index_path(&stored.id);
+        assert!(index_path.is_file());
+        assert!(IndexMetadata::sidecar_path(&index_path).is_file());
+        assert_eq!(
+            stored.size_on_disk,
+            fs::metadata(&index_path).unwrap().len()
+        );
+
+        // building a folder again replaces its entry
+        IndexCatalog::rename(&stored.id, "Documents").unwrap();
+        let rebuilt = IndexCatalog::store(docs, &index(docs, 2), &metadata, &rules).unwrap();
+        assert_eq!(rebuilt.id, stored.id);
+        assert_eq!(rebuilt.name, "Documents");
+        assert_eq!(rebuilt.created, stored.created);
+        assert_eq!(rebuilt.document_count, 2);
+        let other = IndexCatalog::store(notes, &index(notes, 1), &metadata, &rules).unwrap();
+        assert_ne!(other.id, stored.id);
+
+        let catalog = IndexCatalog::load();
+        assert_eq!(catalog.entries.len(), 2);
+        assert_eq!(catalog.find_by_root(docs).unwrap().id, stored.id);
+        assert_eq!(catalog.find_by_root(notes).unwrap().id, other.id);
+        assert!(catalog.find_by_root(Path::new("/home/user")).is_none());
+        assert_eq!(catalog.find(&other.id).unwrap().name, "notes");
+
+        IndexCatalog::remove(&stored.id).unwrap();
+        let catalog = IndexCatalog::load();
+        assert_eq!(catalog.entries.len(), 1);
+        assert!(catalog.find_by_root(docs).is_none());
+        assert!(!index_path.exists());
+        assert!(!IndexMetadata::sidecar_path(&index_path).exists());
+        // unknown ids are left alone
+        IndexCatalog::rename("unknown", "name").unwrap();
+        IndexCatalog::remove("unknown").unwrap();
+        assert_eq!(IndexCatalog::load().entries.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn entries_without_rules_use_the_settings() {
+        let mut entry = CatalogEntry {
+            id: String::from("id"),
+            name: String::from("docs"),
+            root: PathBuf::from("/home/user/docs"),
+            created: 0,
+            updated: 0,
+            document_count: 0,
+            size_on_disk: 0,
+            rules: None,
+        };
+        let settings = Settings {
+            max_file_size: 42,
+            ..Settings::default()
+        };
+        assert_eq!(entry.rules(&settings), IndexRules::from_settings(&settings));
+        let rules = IndexRules {
+            max_depth: 3,
+            ..IndexRules::default()
+        };
+        entry.rules = Some(rules.clone());
+        assert_eq!(entry.rules(&settings), rules);
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::models::archive::{split_entry_path, with_temp_copy, EntryCache};
+use crate::models::extract::{Extraction, ExtractorRegistry};
+
+/// reads a document as text for snippets and previews, through the extractor of its type.
+/// An entry of an archive is extracted on demand. Invalid utf-8 is replaced.
+pub fn load_text(path: &Path) -> Result<String, String> {
+    DocumentReader::default().load_text(path)
+}
+
+/// like load_text() but keeps at most `max_bytes` of the text, a plain text file is not read
+/// any further. Binary files are recognized from their first bytes and not read at all.
+pub fn load_preview(path: &Path, max_bytes: usize) -> Result<String, String> {
+    DocumentReader::with_limit(max_bytes).load_text(path)
+}
+
+/// Reads the documents of one search: the phrase checks and the snippets share the entries
+/// read from the archives.
+pub struct DocumentReader {
+    entries: RefCell<EntryCache>,
+    max_bytes: usize,
+}
+
+impl Default for DocumentReader {
+    fn default() -> Self {
+        Self::with_limit(usize::MAX)
+    }
+}
+
+impl DocumentReader {
+    pub fn with_limit(max_bytes: usize) -> Self {
+        Self {
+            entries: RefCell::new(EntryCache::default()),
+            max_bytes,
+        }
+    }
+    /// the documents about to be read, the archive entries among them are kept when their
+    /// archive is read for one of them
+    pub fn expect<'a, I>(&self, paths: I)
+    where
+        I: IntoIterator<Item = &'a Path>,
+    {
+        self.entries.borrow_mut().expect(paths);
+    }
+    pub fn load_text(&self, path: &Path) -> Result<String, String> {
+        let error = |e: String| format!("could not read {}: {}", path.to_string_lossy(), e);
+        let Some((archive, name)) = split_entry_path(path) else {
+            return read_text(path, self.max_bytes).map_err(error);
+        };
+        let content = self
+            .entries
+            .borrow_mut()
+            .read(&archive, &name)
+            .map_err(error)?;
+        with_temp_copy(&name, &content, |copy| read_text(copy, self.max_bytes))
+            .map_err(|e| error(e.to_string()))?
+            .map_err(error)
+    }
+}
+
+fn read_text(path: &Path, max_bytes: usize) -> Result<String, String> {
+    match ExtractorRegistry::builtin().display_text(path) {
+        Extraction::Plain => read_prefix(path, max_bytes)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .map_err(|e| e.to_string()),
+        Extraction::Text(mut text) => {
+            let mut end = text.len().min(max_bytes);
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+            Ok(text)
+        }
+        Extraction::Binary(mime) => Err(format!("{} files have no text", mime)),
+        Extraction::Failed(e) => Err(e),
+    }
+}
+
+fn read_prefix(path: &Path, max_bytes: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    fs::File::open(path)?
+        .take(max_bytes as u64)
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
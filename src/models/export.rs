@@ -0,0 +1,235 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::models::search_model::SearchHit;
+use crate::utils::format_timestamp;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Markdown,
+}
+
+/// every format, in the order the save dialog offers them
+pub const EXPORT_FORMATS: [ExportFormat; 3] = [
+    ExportFormat::Csv,
+    ExportFormat::JsonLines,
+    ExportFormat::Markdown,
+];
+
+/// one exported result, the snippets are joined in a single column
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    path: String,
+    score: f32,
+    size: Option<u64>,
+    /// local time as format_timestamp() writes it, like in the other formats
+    modified: Option<String>,
+    snippet: &'a str,
+}
+
+impl ExportFormat {
+    /// `csv`, `jsonl` (or `json`) and `markdown` (or `md`)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "jsonl" | "json" => Some(ExportFormat::JsonLines),
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            _ => None,
+        }
+    }
+    /// the format matching the extension of `path`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|extension| Self::parse(&extension.to_string_lossy()))
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::JsonLines => "JSON Lines",
+            ExportFormat::Markdown => "Markdown table",
+        }
+    }
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Markdown => "md",
+        }
+    }
+}
+
+/// writes `hits` in `format`: path, score, size, modification time (local time, see
+/// format_timestamp()) and snippets
+pub fn write_results<W: Write>(
+    hits: &[SearchHit],
+    format: ExportFormat,
+    writer: &mut W,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            writeln!(writer, "path,score,size,modified,snippet")?;
+            for hit in hits {
+                let fields = [
+                    hit.path.to_string_lossy().to_string(),
+                    hit.score.to_string(),
+                    hit.size.map(|size| size.to_string()).unwrap_or_default(),
+                    hit.modified.map(format_timestamp).unwrap_or_default(),
+                    snippet_text(hit),
+                ];
+                let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                writeln!(writer, "{}", fields.join(","))?;
+            }
+        }
+        ExportFormat::JsonLines => {
+            for hit in hits {
+                let snippet = snippet_text(hit);
+                let row = ExportRow {
+                    path: hit.path.to_string_lossy().to_string(),
+                    score: hit.score,
+                    size: hit.size,
+                    modified: hit.modified.map(format_timestamp),
+                    snippet: &snippet,
+                };
+                serde_json::to_writer(&mut *writer, &row)?;
+                writeln!(writer)?;
+            }
+        }
+        ExportFormat::Markdown => {
+            writeln!(writer, "| Path | Score | Size | Modified | Snippet |")?;
+            writeln!(writer, "| --- | ---: | ---: | --- | --- |")?;
+            for hit in hits {
+                writeln!(
+                    writer,
+                    "| {} | {:.4} | {} | {} | {} |",
+                    markdown_cell(&hit.path.to_string_lossy()),
+                    hit.score,
+                    hit.size.map(|size| size.to_string()).unwrap_or_default(),
+                    hit.modified.map(format_timestamp).unwrap_or_default(),
+                    markdown_cell(&snippet_text(hit))
+                )?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+fn snippet_text(hit: &SearchHit) -> String {
+    hit.snippets
+        .iter()
+        .map(|snippet| snippet.text.as_str())
+        .collect::<Vec<&str>>()
+        .join(" ... ")
+}
+
+/// quotes the field when it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// a table cell must stay on one line and can't contain an unescaped pipe
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::models::snippet::Snippet;
+
+    /// modification time of the first hit
+    const MODIFIED: u64 = 1_700_000_000;
+
+    fn hits() -> Vec<SearchHit> {
+        let mut first = SearchHit::new(PathBuf::from("/docs/a, b.txt"), 0.5, None);
+        first.size = Some(1200);
+        first.modified = Some(MODIFIED);
+        first.snippets = ["say \"hi\"", "line\nbreak | pipe"]
+            .into_iter()
+            .map(|text| Snippet {
+                text: text.to_string(),
+                highlights: Vec::new(),
+            })
+            .collect();
+        vec![
+            first,
+            SearchHit::new(PathBuf::from("/docs/c.md"), 0.25, None),
+        ]
+    }
+
+    fn export(format: ExportFormat) -> String {
+        let mut output = Vec::new();
+        write_results(&hits(), format, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn csv_field_quotes_only_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_has_a_header_and_a_row_per_hit() {
+        assert_eq!(
+            export(ExportFormat::Csv),
+            format!(
+                "path,score,size,modified,snippet\n\
+                 \"/docs/a, b.txt\",0.5,1200,{},\"say \"\"hi\"\" ... line\nbreak | pipe\"\n\
+                 /docs/c.md,0.25,,,\n",
+                format_timestamp(MODIFIED)
+            )
+        );
+    }
+
+    #[test]
+    fn json_lines_has_an_object_per_hit() {
+        let output = export(ExportFormat::JsonLines);
+        let rows: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["path"], "/docs/a, b.txt");
+        assert_eq!(rows[0]["size"], 1200);
+        assert_eq!(rows[0]["modified"], format_timestamp(MODIFIED));
+        assert_eq!(rows[0]["snippet"], "say \"hi\" ... line\nbreak | pipe");
+        assert!(rows[1]["modified"].is_null());
+    }
+
+    #[test]
+    fn markdown_cells_stay_on_one_line() {
+        let output = export(ExportFormat::Markdown);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[2],
+            format!(
+                "| /docs/a, b.txt | 0.5000 | 1200 | {} | say \"hi\" ... line break \\| pipe |",
+                format_timestamp(MODIFIED)
+            )
+        );
+        assert_eq!(lines[3], "| /docs/c.md | 0.2500 |  |  |  |");
+    }
+
+    #[test]
+    fn format_from_name_or_extension() {
+        assert_eq!(ExportFormat::parse("JSON"), Some(ExportFormat::JsonLines));
+        assert_eq!(ExportFormat::parse("xml"), None);
+        for format in EXPORT_FORMATS {
+            let path = PathBuf::from(format!("results.{}", format.extension()));
+            assert_eq!(ExportFormat::from_path(&path), Some(format));
+        }
+    }
+}
@@ -0,0 +1,608 @@
+use std::{
+    fs,
+    io::{self, Read},
+    panic,
+    path::Path,
+};
+
+use gtk::gio;
+
+/// bytes read from the start of a file to guess its type
+const SNIFF_LENGTH: usize = 4096;
+
+/// Turns a document of some MIME type into the plain text that gets indexed, previewed and
+/// searched for snippets. Extractors are tried in the order they are registered.
+pub trait Extractor: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// whether this extractor reads documents of type `mime`, as guessed by gio
+    fn handles(&self, mime: &str) -> bool;
+    fn extract(&self, path: &Path) -> Result<String, String>;
+    /// the text given to the index, by default the extracted text itself
+    fn searchable(&self, text: String) -> String {
+        text
+    }
+}
+
+/// what the registry made of a file
+pub enum Extraction {
+    /// readable as it is, the file itself is indexed
+    Plain,
+    Text(String),
+    /// no extractor for this type and not text, the file is skipped
+    Binary(String),
+    Failed(String),
+}
+
+/// the extractors used while indexing and reading documents back
+pub struct ExtractorRegistry {
+    extractors: Vec<Box<dyn Extractor>>,
+}
+
+impl Default for ExtractorRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ExtractorRegistry {
+    pub fn builtin() -> Self {
+        Self {
+            extractors: vec![
+                Box::new(MarkdownExtractor),
+                Box::new(HtmlExtractor),
+                Box::new(PdfExtractor),
+                Box::new(EpubExtractor),
+                Box::new(OfficeExtractor),
+                Box::new(SourceCodeExtractor),
+            ],
+        }
+    }
+    /// adds an extractor, tried before the built-in ones
+    pub fn register(&mut self, extractor: Box<dyn Extractor>) {
+        self.extractors.insert(0, extractor);
+    }
+    /// the text of `path` as it should be indexed
+    pub fn searchable_text(&self, path: &Path) -> Extraction {
+        self.run(path, true)
+    }
+    /// the text of `path` as it should be shown
+    pub fn display_text(&self, path: &Path) -> Extraction {
+        self.run(path, false)
+    }
+    fn run(&self, path: &Path, searchable: bool) -> Extraction {
+        let mime = match guess_type(path) {
+            Ok(mime) => mime,
+            Err(e) => return Extraction::Failed(e.to_string()),
+        };
+        let Some(extractor) = self
+            .extractors
+            .iter()
+            .find(|extractor| extractor.handles(&mime))
+        else {
+            return if is_text(&mime, path) {
+                Extraction::Plain
+            } else {
+                Extraction::Binary(mime)
+            };
+        };
+        match extractor.extract(path) {
+            Ok(text) if searchable => Extraction::Text(extractor.searchable(text)),
+            Ok(text) => Extraction::Text(text),
+            Err(e) => Extraction::Failed(format!("{} extractor: {}", extractor.name(), e)),
+        }
+    }
+}
+
+/// the MIME type of `path`, from its name and first bytes
+pub fn guess_type(path: &Path) -> io::Result<String> {
+    let mut prefix = Vec::with_capacity(SNIFF_LENGTH);
+    fs::File::open(path)?
+        .take(SNIFF_LENGTH as u64)
+        .read_to_end(&mut prefix)?;
+    let (mime, _uncertain) = gio::content_type_guess(Some(path), &prefix);
+    Ok(mime.to_string())
+}
+
+/// text types, and unknown types whose first bytes look like utf-8 text
+fn is_text(mime: &str, path: &Path) -> bool {
+    if gio::content_type_is_a(mime, "text/plain") {
+        return true;
+    }
+    if !gio::content_type_is_unknown(mime) {
+        return false;
+    }
+    let mut prefix = Vec::with_capacity(SNIFF_LENGTH);
+    let read = fs::File::open(path)
+        .and_then(|file| file.take(SNIFF_LENGTH as u64).read_to_end(&mut prefix));
+    read.is_ok() && !prefix.contains(&0) && std::str::from_utf8(&prefix).is_ok()
+}
+
+fn read_string(path: &Path) -> Result<String, String> {
+    fs::read(path)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// the entries of a zip file whose name passes `wanted`, in archive order
+fn read_zip_entries<F>(path: &Path, wanted: F) -> Result<Vec<String>, String>
+where
+    F: Fn(&str) -> bool,
+{
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        if !wanted(entry.name()) {
+            continue;
+        }
+        let mut content = String::new();
+        entry
+            .read_to_string(&mut content)
+            .map_err(|e| format!("{}: {}", entry.name(), e))?;
+        entries.push(content);
+    }
+    Ok(entries)
+}
+
+struct MarkdownExtractor;
+
+impl Extractor for MarkdownExtractor {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+    fn handles(&self, mime: &str) -> bool {
+        mime == "text/markdown" || mime == "text/x-markdown"
+    }
+    fn extract(&self, path: &Path) -> Result<String, String> {
+        Ok(strip_markdown(&read_string(path)?))
+    }
+}
+
+struct HtmlExtractor;
+
+impl Extractor for HtmlExtractor {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+    fn handles(&self, mime: &str) -> bool {
+        mime == "text/html" || mime == "application/xhtml+xml"
+    }
+    fn extract(&self, path: &Path) -> Result<String, String> {
+        Ok(html_text(&read_string(path)?))
+    }
+}
+
+struct PdfExtractor;
+
+impl Extractor for PdfExtractor {
+    fn name(&self) -> &'static str {
+        "pdf"
+    }
+    fn handles(&self, mime: &str) -> bool {
+        mime == "application/pdf"
+    }
+    fn extract(&self, path: &Path) -> Result<String, String> {
+        // pdf-extract panics on some malformed files, which must not end the indexing job
+        panic::catch_unwind(|| pdf_extract::extract_text(path))
+            .map_err(|_| String::from("the pdf could not be parsed"))?
+            .map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// the chapters of an epub are xhtml files inside a zip
+struct EpubExtractor;
+
+impl Extractor for EpubExtractor {
+    fn name(&self) -> &'static str {
+        "epub"
+    }
+    fn handles(&self, mime: &str) -> bool {
+        mime == "application/epub+zip"
+    }
+    fn extract(&self, path: &Path) -> Result<String, String> {
+        let chapters = read_zip_entries(path, |name| {
+            name.ends_with(".xhtml") || name.ends_with(".html") || name.ends_with(".htm")
+        })?;
+        Ok(chapters
+            .iter()
+            .map(|chapter| html_text(chapter))
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
+
+/// OpenDocument and DOCX files keep their text in an xml file inside a zip
+struct OfficeExtractor;
+
+impl Extractor for OfficeExtractor {
+    fn name(&self) -> &'static str {
+        "office"
+    }
+    fn handles(&self, mime: &str) -> bool {
+        mime.starts_with("application/vnd.oasis.opendocument.")
+            || mime == "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    }
+    fn extract(&self, path: &Path) -> Result<String, String> {
+        let content = read_zip_entries(path, |name| {
+            name == "content.xml" || name == "word/document.xml"
+        })?;
+        Ok(content.iter().map(|xml| strip_tags(xml)).collect())
+    }
+}
+
+/// MIME types of source files that are not a subtype of text/x-*
+const SOURCE_TYPES: [&str; 9] = [
+    "text/rust",
+    "text/javascript",
+    "application/javascript",
+    "application/x-typescript",
+    "application/x-shellscript",
+    "application/x-php",
+    "application/x-perl",
+    "application/x-ruby",
+    "application/sql",
+];
+
+/// Source files are shown as they are, but the words of compound identifiers are indexed
+/// too, so that `filter` finds `IndexFilter` and `index_file`.
+struct SourceCodeExtractor;
+
+impl Extractor for SourceCodeExtractor {
+    fn name(&self) -> &'static str {
+        "source code"
+    }
+    fn handles(&self, mime: &str) -> bool {
+        mime.starts_with("text/x-") || SOURCE_TYPES.contains(&mime)
+    }
+    fn extract(&self, path: &Path) -> Result<String, String> {
+        read_string(path)
+    }
+    fn searchable(&self, text: String) -> String {
+        let mut parts = Vec::new();
+        for identifier in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+            let words = split_identifier(identifier);
+            if words.len() > 1 {
+                parts.extend(words);
+            }
+        }
+        if parts.is_empty() {
+            return text;
+        }
+        format!("{}\n{}", text, parts.join(" "))
+    }
+}
+
+/// `IndexFilter` and `index_filter` both give ["index", "filter"]
+fn split_identifier(identifier: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in identifier.chars() {
+        if (c == '_' || (c.is_uppercase() && previous_lower)) && !current.is_empty() {
+            words.push(current.to_lowercase());
+            current.clear();
+        }
+        if c != '_' {
+            current.push(c);
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+    }
+    if !current.is_empty() {
+        words.push(current.to_lowercase());
+    }
+    words
+}
+
+/// the title of an html page followed by its text
+fn html_text(html: &str) -> String {
+    let lower = html.to_lowercase();
+    let title = lower
+        .find("<title")
+        .and_then(|start| Some(start + lower[start..].find('>')? + 1))
+        .and_then(|start| Some((start, start + lower[start..].find("</title")?)))
+        .map(|(start, end)| decode_entities(html[start..end].trim()));
+    let body = strip_tags(html);
+    match title {
+        // the title lives in the head, which strip_tags leaves out
+        Some(title) if !title.is_empty() => format!("{}\n\n{}", title, body),
+        _ => body,
+    }
+}
+
+/// elements whose content is not text
+const SKIPPED_ELEMENTS: [&str; 4] = ["head", "script", "style", "noscript"];
+/// elements ending a line, the local name is compared so it also works for odf (`text:p`) and
+/// docx (`w:p`) xml
+const BLOCK_ELEMENTS: [&str; 14] = [
+    "p", "div", "br", "li", "tr", "h", "h1", "h2", "h3", "h4", "h5", "h6", "section", "tab",
+];
+
+/// the text of an html or xml document, one line per paragraph
+pub fn strip_tags(markup: &str) -> String {
+    let mut text = String::new();
+    let mut rest = markup;
+    let mut skipped: Option<&str> = None;
+    while let Some(start) = rest.find('<') {
+        if skipped.is_none() {
+            text.push_str(&decode_entities(&rest[..start]));
+        }
+        // a lone `<`, as in `1 < 2`, is text
+        let Some(end) = rest[start..].find('>') else {
+            if skipped.is_none() {
+                text.push_str(&decode_entities(&rest[start..]));
+            }
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        if tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        let local_name = name.rsplit(':').next().unwrap_or("").to_string();
+        match skipped {
+            Some(element) if closing && element == local_name => skipped = None,
+            Some(_) => {}
+            None if !closing && !tag.ends_with('/') => {
+                if let Some(element) = SKIPPED_ELEMENTS.iter().find(|e| **e == local_name) {
+                    skipped = Some(*element);
+                }
+            }
+            None => {}
+        }
+        if skipped.is_none() && BLOCK_ELEMENTS.contains(&local_name.as_str()) {
+            text.push(if local_name == "tab" { ' ' } else { '\n' });
+        }
+    }
+    if skipped.is_none() {
+        text.push_str(&decode_entities(rest));
+    }
+    collapse_blank_lines(&text)
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 8)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#')?.parse().ok())
+                .and_then(char::from_u32),
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// the text of a markdown document without its markup: headings, quotes, list markers,
+/// emphasis, code fences and link targets are removed
+pub fn strip_markdown(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            continue;
+        }
+        let is_rule = trimmed.len() >= 3
+            && trimmed
+                .chars()
+                .all(|c| c == '-' || c == '*' || c == '_' || c == ' ');
+        if is_rule {
+            lines.push(String::new());
+            continue;
+        }
+        let mut content = trimmed.trim_start_matches(['#', '>', ' ']);
+        for marker in ["- [ ] ", "- [x] ", "- ", "* ", "+ "] {
+            if let Some(stripped) = content.strip_prefix(marker) {
+                content = stripped;
+                break;
+            }
+        }
+        let digits = content.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits > 0 {
+            if let Some(stripped) = content[digits..].strip_prefix(". ") {
+                content = stripped;
+            }
+        }
+        lines.push(strip_inline_markdown(content));
+    }
+    collapse_blank_lines(&lines.join("\n"))
+}
+
+/// `[text](url)` and `![alt](url)` become their text, emphasis and code markers are dropped
+fn strip_inline_markdown(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut text = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '!' if chars.get(i + 1) == Some(&'[') => i += 1,
+            '[' => match closing(&chars, i, '[', ']') {
+                Some(close) if chars.get(close + 1) == Some(&'(') => {
+                    let label: String = chars[i + 1..close].iter().collect();
+                    text.push_str(&strip_inline_markdown(&label));
+                    i = closing(&chars, close + 1, '(', ')').map_or(chars.len(), |end| end + 1);
+                }
+                _ => {
+                    text.push('[');
+                    i += 1;
+                }
+            },
+            '*' | '`' | '~' | '|' => {
+                if chars[i] == '|' {
+                    text.push(' ');
+                }
+                i += 1;
+            }
+            // underscores inside words are part of them, e.g. snake_case
+            '_' => {
+                let inside_word = i > 0
+                    && chars[i - 1].is_alphanumeric()
+                    && chars.get(i + 1).is_some_and(|c| c.is_alphanumeric());
+                if inside_word {
+                    text.push('_');
+                }
+                i += 1;
+            }
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    text
+}
+
+/// the position of the `close` matching the `open` at `start`, nested pairs included, as in
+/// `[a [b](c)](d)` or `(https://en.wikipedia.org/wiki/Rust_(language))`
+fn closing(chars: &[char], start: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in chars.iter().enumerate().skip(start) {
+        if *c == open {
+            depth += 1;
+        } else if *c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+/// trims the lines and keeps at most one empty line in a row
+fn collapse_blank_lines(text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() && lines.last().map_or(true, |last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_tags_keeps_the_text_of_the_body() {
+        let html = "<html><head><title>T</title><style>p { color: red }</style></head>\
+                    <body><p>first</p><p>second <b>bold</b></p><script>var x = 1;</script>\
+                    </body></html>";
+        assert_eq!(strip_tags(html), "first\n\nsecond bold");
+    }
+
+    #[test]
+    fn strip_tags_reads_office_xml() {
+        let xml = "<?xml version=\"1.0\"?><w:body><w:p><w:r><w:t>one</w:t></w:r></w:p>\
+                   <w:p><w:t>two</w:t><w:tab/><w:t>three</w:t></w:p></w:body>";
+        assert_eq!(strip_tags(xml), "one\n\ntwo three");
+    }
+
+    #[test]
+    fn strip_tags_keeps_an_unterminated_tag_as_text() {
+        assert_eq!(strip_tags("<p>1 < 2"), "1 < 2");
+        assert_eq!(strip_tags("<script>1 < 2"), "");
+    }
+
+    #[test]
+    fn decode_entities_replaces_known_entities() {
+        assert_eq!(
+            decode_entities("a &amp; b &lt;c&gt; &quot;d&quot;"),
+            "a & b <c> \"d\""
+        );
+        assert_eq!(decode_entities("&#65;&#x42;"), "AB");
+    }
+
+    #[test]
+    fn decode_entities_keeps_what_is_not_an_entity() {
+        assert_eq!(decode_entities("AT&T"), "AT&T");
+        assert_eq!(decode_entities("&unknown;"), "&unknown;");
+        // longer than 8 characters, not looked up
+        assert_eq!(decode_entities("&averylongname;"), "&averylongname;");
+        assert_eq!(decode_entities("&amp"), "&amp");
+    }
+
+    #[test]
+    fn strip_markdown_removes_the_markup() {
+        let markdown = "# Title\n\n> quoted\n\n- item\n1. first\n\n```rust\nlet x = 1;\n```\n---\n**bold** and `code`";
+        assert_eq!(
+            strip_markdown(markdown),
+            "Title\n\nquoted\n\nitem\nfirst\n\nlet x = 1;\n\nbold and code"
+        );
+    }
+
+    #[test]
+    fn strip_inline_markdown_keeps_the_text_of_links() {
+        assert_eq!(
+            strip_inline_markdown("see [the docs](https://example.com) and ![a logo](logo.png)"),
+            "see the docs and a logo"
+        );
+        assert_eq!(strip_inline_markdown("[a [b](c)](d) after"), "a b after");
+        assert_eq!(
+            strip_inline_markdown("[Rust](https://en.wikipedia.org/wiki/Rust_(language)) is"),
+            "Rust is"
+        );
+        assert_eq!(
+            strip_inline_markdown("[not a link] [x]"),
+            "[not a link] [x]"
+        );
+    }
+
+    #[test]
+    fn strip_inline_markdown_keeps_underscores_inside_words() {
+        assert_eq!(
+            strip_inline_markdown("snake_case and _emphasis_"),
+            "snake_case and emphasis"
+        );
+    }
+
+    #[test]
+    fn split_identifier_splits_camel_and_snake_case() {
+        assert_eq!(split_identifier("IndexFilter"), ["index", "filter"]);
+        assert_eq!(split_identifier("index_filter"), ["index", "filter"]);
+        assert_eq!(split_identifier("__init__"), ["init"]);
+        assert_eq!(split_identifier("parse2Html"), ["parse2", "html"]);
+        assert_eq!(split_identifier("word"), ["word"]);
+    }
+}
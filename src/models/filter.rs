@@ -0,0 +1,334 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use gtk::glib;
+
+use crate::models::metadata::FileMetadata;
+
+/// the fields a filter can be written on, `ext:pdf`, `path:projects/`, `modified:>2025-01-01`
+/// and `size:<2MB`
+pub const FILTER_FIELDS: [&str; 4] = ["ext", "path", "modified", "size"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    /// splits the operator in front of `value`, no operator means Equal
+    fn split(value: &str) -> (Self, &str) {
+        for (prefix, comparison) in [
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+            ("=", Comparison::Equal),
+        ] {
+            if let Some(rest) = value.strip_prefix(prefix) {
+                return (comparison, rest);
+            }
+        }
+        (Comparison::Equal, value)
+    }
+    fn compare(&self, value: u64, reference: u64) -> bool {
+        match self {
+            Comparison::Less => value < reference,
+            Comparison::LessOrEqual => value <= reference,
+            Comparison::Equal => value == reference,
+            Comparison::GreaterOrEqual => value >= reference,
+            Comparison::Greater => value > reference,
+        }
+    }
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Greater => ">",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterKind {
+    /// lowercased, without the leading dot
+    Extension(String),
+    Path(String),
+    /// unix timestamp of the start of the day, in local time
+    Modified(Comparison, u64),
+    Size(Comparison, u64),
+}
+
+/// A condition on the file of a result, applied to the results of the query before they are
+/// sorted. `source` is the filter as it was written, `-ext:pdf` for a negated one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    pub negated: bool,
+    pub source: String,
+    /// byte range of the filter in the query it was parsed from, a NOT or `-` negating it
+    /// included, so it can be cut out of the query
+    pub span: Range<usize>,
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+impl Filter {
+    /// true when `word` is written like a filter (`field:value`), whether or not the value is
+    /// valid
+    pub fn is_filter(word: &str) -> bool {
+        word.split_once(':')
+            .is_some_and(|(field, _)| FILTER_FIELDS.contains(&field))
+    }
+    /// parses `field:value`, the error explains what is wrong with the value
+    pub fn parse(word: &str) -> Result<Self, String> {
+        let Some((field, value)) = word.split_once(':') else {
+            return Err(format!("{} is not a filter", word));
+        };
+        if value.is_empty() {
+            return Err(format!("{}: expects a value", field));
+        }
+        let kind = match field {
+            "ext" => FilterKind::Extension(value.trim_start_matches('.').to_lowercase()),
+            "path" => FilterKind::Path(value.to_string()),
+            "modified" => {
+                let (comparison, date) = Comparison::split(value);
+                FilterKind::Modified(comparison, parse_date(date)?)
+            }
+            "size" => {
+                let (comparison, size) = Comparison::split(value);
+                FilterKind::Size(comparison, parse_size(size)?)
+            }
+            other => return Err(format!("unknown filter {}:", other)),
+        };
+        Ok(Self {
+            kind,
+            negated: false,
+            source: word.to_string(),
+            span: 0..word.len(),
+        })
+    }
+    pub fn negate(mut self) -> Self {
+        self.negated = !self.negated;
+        self.source = format!("-{}", self.source);
+        self
+    }
+    /// `metadata` is None when the file can't be read anymore, it then only passes negated
+    /// filters on its size or date
+    pub fn matches(&self, path: &Path, metadata: Option<&FileMetadata>) -> bool {
+        let matched = match &self.kind {
+            FilterKind::Extension(extension) => path
+                .extension()
+                .is_some_and(|ext| ext.to_string_lossy().to_lowercase() == *extension),
+            FilterKind::Path(part) => path.to_string_lossy().contains(part.as_str()),
+            // a date stands for the whole day: `>` starts after it and `<=` includes it
+            FilterKind::Modified(Comparison::Equal, day) => metadata
+                .is_some_and(|meta| meta.modified >= *day && meta.modified < day + SECONDS_PER_DAY),
+            FilterKind::Modified(Comparison::Greater, day) => {
+                metadata.is_some_and(|meta| meta.modified >= day + SECONDS_PER_DAY)
+            }
+            FilterKind::Modified(Comparison::LessOrEqual, day) => {
+                metadata.is_some_and(|meta| meta.modified < day + SECONDS_PER_DAY)
+            }
+            FilterKind::Modified(comparison, day) => {
+                metadata.is_some_and(|meta| comparison.compare(meta.modified, *day))
+            }
+            FilterKind::Size(comparison, size) => {
+                metadata.is_some_and(|meta| comparison.compare(meta.size, *size))
+            }
+        };
+        matched != self.negated
+    }
+}
+
+impl fmt::Display for Filter {
+    /// a short label for the filter chips, e.g. "not ext: pdf" or "size: < 2MB"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            write!(f, "not ")?;
+        }
+        let (field, value) = self
+            .source
+            .trim_start_matches('-')
+            .split_once(':')
+            .unwrap_or(("", &self.source));
+        match &self.kind {
+            FilterKind::Modified(comparison, _) | FilterKind::Size(comparison, _) => {
+                let value = value.trim_start_matches(['<', '>', '=']);
+                write!(f, "{}: {} {}", field, comparison.symbol(), value)
+            }
+            _ => write!(f, "{}: {}", field, value),
+        }
+    }
+}
+
+/// "2025-01-01" => unix timestamp of midnight, local time
+fn parse_date(date: &str) -> Result<u64, String> {
+    let invalid = || format!("{} is not a date, expected YYYY-MM-DD", date);
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse(), month.parse(), day.parse()) else {
+        return Err(invalid());
+    };
+    let date = glib::DateTime::from_local(year, month, day, 0, 0, 0.0).map_err(|_| invalid())?;
+    Ok(date.to_unix().max(0) as u64)
+}
+
+/// "2MB", "512k" or "100" (bytes) => bytes, with the units of format_bytes
+fn parse_size(size: &str) -> Result<u64, String> {
+    let invalid = || format!("{} is not a size, expected e.g. 500KB or 2MB", size);
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit.to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(size: u64, modified: u64) -> FileMetadata {
+        FileMetadata {
+            size,
+            modified,
+            hash: None,
+        }
+    }
+
+    #[test]
+    fn is_filter_only_accepts_known_fields() {
+        assert!(Filter::is_filter("ext:pdf"));
+        assert!(Filter::is_filter("size:"));
+        assert!(!Filter::is_filter("http://example.com"));
+        assert!(!Filter::is_filter("ext"));
+    }
+
+    #[test]
+    fn parse_extension_and_path() {
+        let filter = Filter::parse("ext:.PDF").unwrap();
+        assert_eq!(filter.kind, FilterKind::Extension(String::from("pdf")));
+        assert_eq!(filter.span, 0..8);
+        assert_eq!(
+            Filter::parse("path:projects/").unwrap().kind,
+            FilterKind::Path(String::from("projects/"))
+        );
+    }
+
+    #[test]
+    fn parse_size_with_comparison_and_unit() {
+        for (word, kind) in [
+            ("size:100", FilterKind::Size(Comparison::Equal, 100)),
+            ("size:<2MB", FilterKind::Size(Comparison::Less, 2 << 20)),
+            (
+                "size:>=512k",
+                FilterKind::Size(Comparison::GreaterOrEqual, 512 << 10),
+            ),
+            ("size:>1.5KB", FilterKind::Size(Comparison::Greater, 1536)),
+        ] {
+            assert_eq!(Filter::parse(word).unwrap().kind, kind, "{}", word);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        for word in [
+            "ext:",
+            "size:big",
+            "size:2XB",
+            "modified:yesterday",
+            "modified:2025-13-01",
+            "name:x",
+        ] {
+            assert!(Filter::parse(word).is_err(), "{}", word);
+        }
+    }
+
+    #[test]
+    fn parse_modified_is_the_start_of_the_day() {
+        let filter = Filter::parse("modified:>2025-01-01").unwrap();
+        let start = parse_date("2025-01-01").unwrap();
+        assert_eq!(
+            filter.kind,
+            FilterKind::Modified(Comparison::Greater, start)
+        );
+    }
+
+    #[test]
+    fn modified_compares_whole_days() {
+        let day = parse_date("2025-01-01").unwrap();
+        let noon = file(0, day + SECONDS_PER_DAY / 2);
+        let next_day = file(0, day + SECONDS_PER_DAY);
+        let path = Path::new("a.txt");
+        let matches = |word: &str, metadata: &FileMetadata| {
+            Filter::parse(word).unwrap().matches(path, Some(metadata))
+        };
+        assert!(matches("modified:2025-01-01", &noon));
+        assert!(!matches("modified:2025-01-01", &next_day));
+        assert!(!matches("modified:>2025-01-01", &noon));
+        assert!(matches("modified:>2025-01-01", &next_day));
+        assert!(matches("modified:<=2025-01-01", &noon));
+        assert!(!matches("modified:<=2025-01-01", &next_day));
+        assert!(!matches("modified:<2025-01-01", &noon));
+        assert!(matches("modified:>=2025-01-01", &noon));
+    }
+
+    #[test]
+    fn matches_extension_path_and_size() {
+        let small = file(100, 0);
+        let pdf = Path::new("/docs/projects/Report.PDF");
+        assert!(Filter::parse("ext:pdf").unwrap().matches(pdf, Some(&small)));
+        assert!(Filter::parse("path:projects").unwrap().matches(pdf, None));
+        assert!(Filter::parse("size:<1KB")
+            .unwrap()
+            .matches(pdf, Some(&small)));
+        assert!(!Filter::parse("size:>1KB")
+            .unwrap()
+            .matches(pdf, Some(&small)));
+    }
+
+    #[test]
+    fn negated_filters_invert_the_match() {
+        let filter = Filter::parse("ext:pdf").unwrap().negate();
+        assert_eq!(filter.source, "-ext:pdf");
+        assert!(!filter.matches(Path::new("a.pdf"), None));
+        assert!(filter.matches(Path::new("a.txt"), None));
+        // a file that can't be read passes negated size and date filters only
+        assert!(!Filter::parse("size:<1KB")
+            .unwrap()
+            .matches(Path::new("a"), None));
+        assert!(Filter::parse("size:<1KB")
+            .unwrap()
+            .negate()
+            .matches(Path::new("a"), None));
+    }
+
+    #[test]
+    fn display_is_a_short_label() {
+        assert_eq!(
+            Filter::parse("ext:pdf").unwrap().negate().to_string(),
+            "not ext: pdf"
+        );
+        assert_eq!(
+            Filter::parse("size:<2MB").unwrap().to_string(),
+            "size: < 2MB"
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::{fs, path::PathBuf};
+
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+
+use crate::models::catalog::{unix_now, IndexCatalog};
+use crate::types::StandardResult;
+use crate::utils::write_json_atomic;
+
+/// number of executed queries kept, the oldest are dropped first
+const MAX_HISTORY: usize = 500;
+
+/// one query searched explicitly (Enter or the search button)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub query: String,
+    /// unix timestamp in seconds
+    pub timestamp: u64,
+    pub index_id: String,
+    pub index_name: String,
+    pub hits: usize,
+}
+
+/// a query pinned by the user, listed in the "Saved searches" menu
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub query: String,
+    pub created: u64,
+}
+
+/// a completion offered under the search entry
+pub struct Suggestion {
+    pub query: String,
+    pub pinned: bool,
+}
+
+/// The executed queries, most recent first, and the saved searches. Stored as json next to
+/// the catalog.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SearchHistory {
+    pub entries: Vec<HistoryEntry>,
+    pub saved: Vec<SavedSearch>,
+}
+
+impl SearchHistory {
+    fn path() -> PathBuf {
+        IndexCatalog::storage_dir().join("history.json")
+    }
+    /// a missing or unreadable history is treated as an empty one
+    pub fn load() -> Self {
+        let path = Self::path();
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                error!("the search history {:?} could not be parsed: {}", path, e);
+                Self::default()
+            }),
+            Err(e) => {
+                debug!("no search history loaded from {:?}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+    pub fn save(&self) -> StandardResult {
+        write_json_atomic(self, &Self::path())
+    }
+    pub fn record(&mut self, query: &str, index_id: &str, index_name: &str, hits: usize) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        self.entries.insert(
+            0,
+            HistoryEntry {
+                query: query.to_string(),
+                timestamp: unix_now(),
+                index_id: index_id.to_string(),
+                index_name: index_name.to_string(),
+                hits,
+            },
+        );
+        self.entries.truncate(MAX_HISTORY);
+    }
+    pub fn is_pinned(&self, query: &str) -> bool {
+        self.saved.iter().any(|saved| saved.query == query.trim())
+    }
+    /// pins `query` if it isn't yet, unpins it otherwise. Returns whether it is now pinned.
+    pub fn toggle_pin(&mut self, query: &str) -> bool {
+        let query = query.trim();
+        if self.is_pinned(query) {
+            self.saved.retain(|saved| saved.query != query);
+            false
+        } else if query.is_empty() {
+            false
+        } else {
+            self.saved.push(SavedSearch {
+                query: query.to_string(),
+                created: unix_now(),
+            });
+            true
+        }
+    }
+    /// the saved searches then the recent queries starting with `prefix` (case insensitive),
+    /// without duplicates and without `prefix` itself
+    pub fn suggestions(&self, prefix: &str, max: usize) -> Vec<Suggestion> {
+        let prefix = prefix.trim().to_lowercase();
+        let mut suggestions: Vec<Suggestion> = Vec::new();
+        let saved = self.saved.iter().map(|saved| (saved.query.as_str(), true));
+        let recent = self
+            .entries
+            .iter()
+            .map(|entry| (entry.query.as_str(), false));
+        for (query, pinned) in saved.chain(recent) {
+            let lowercase = query.to_lowercase();
+            if suggestions.len() == max {
+                break;
+            }
+            if lowercase == prefix
+                || !lowercase.starts_with(&prefix)
+                || suggestions
+                    .iter()
+                    .any(|suggestion| suggestion.query == query)
+            {
+                continue;
+            }
+            suggestions.push(Suggestion {
+                query: query.to_string(),
+                pinned,
+            });
+        }
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(recent: &[&str], saved: &[&str]) -> SearchHistory {
+        let mut history = SearchHistory::default();
+        for query in recent.iter().rev() {
+            history.record(query, "id", "index", 0);
+        }
+        for query in saved {
+            history.toggle_pin(query);
+        }
+        history
+    }
+
+    fn queries(suggestions: Vec<Suggestion>) -> Vec<(String, bool)> {
+        suggestions
+            .into_iter()
+            .map(|suggestion| (suggestion.query, suggestion.pinned))
+            .collect()
+    }
+
+    #[test]
+    fn record_keeps_the_most_recent_first() {
+        let mut history = SearchHistory::default();
+        history.record(" first ", "id", "index", 3);
+        history.record("second", "id", "index", 0);
+        history.record("   ", "id", "index", 0);
+        let recorded: Vec<&str> = history
+            .entries
+            .iter()
+            .map(|entry| entry.query.as_str())
+            .collect();
+        assert_eq!(recorded, ["second", "first"]);
+    }
+
+    #[test]
+    fn toggle_pin_pins_then_unpins() {
+        let mut history = SearchHistory::default();
+        assert!(history.toggle_pin(" rust gtk "));
+        assert!(history.is_pinned("rust gtk"));
+        assert!(!history.toggle_pin("rust gtk"));
+        assert!(!history.is_pinned("rust gtk"));
+        assert!(!history.toggle_pin("  "));
+        assert!(history.saved.is_empty());
+    }
+
+    #[test]
+    fn suggestions_list_saved_searches_first() {
+        let history = history(&["rust book", "Rust gtk", "python"], &["rust gtk"]);
+        assert_eq!(
+            queries(history.suggestions("RU", 10)),
+            [
+                (String::from("rust gtk"), true),
+                (String::from("rust book"), false),
+                (String::from("Rust gtk"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn suggestions_skip_the_prefix_itself_and_duplicates() {
+        let history = history(&["rust", "rust book", "rust book"], &[]);
+        assert_eq!(
+            queries(history.suggestions("rust", 10)),
+            [(String::from("rust book"), false)]
+        );
+    }
+
+    #[test]
+    fn suggestions_are_limited() {
+        let history = history(&["a1", "a2", "a3"], &[]);
+        assert_eq!(history.suggestions("a", 2).len(), 2);
+        assert_eq!(history.suggestions("", 10).len(), 3);
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use log::warn;
+
+/// the ignore files read in every folder, in the order they take precedence
+const IGNORE_FILE: &str = ".ignore";
+const GIT_IGNORE_FILE: &str = ".gitignore";
+/// only read in the folder holding the `.git` of a repository
+const GIT_EXCLUDE_FILE: &str = ".git/info/exclude";
+
+/// Tells whether a path is left out by the ignore files, with the precedence
+/// `ignore::WalkBuilder` gives them: `.ignore` wins over `.gitignore`, which wins over
+/// `.git/info/exclude` and then the global gitignore. For each of them the deepest folder
+/// with a matching line decides, and the last matching line of a file, so `!pattern` brings
+/// a file back. As with git, the .gitignore of a folder above a repository does not apply to
+/// it.
+///
+/// The files are parsed once, a matcher is built again when they may have changed.
+#[derive(Clone)]
+pub struct IgnoreMatcher {
+    global: Arc<Gitignore>,
+    /// the parsed ignore files by path, None when there is no such file
+    parsed: Arc<Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>>,
+    /// whether a folder holds a `.git`
+    repositories: Arc<Mutex<HashMap<PathBuf, bool>>>,
+}
+
+impl IgnoreMatcher {
+    pub fn new() -> Self {
+        let (global, error) = Gitignore::global();
+        if let Some(e) = error {
+            warn!("the global gitignore is partly ignored: {}", e);
+        }
+        Self::with_global(global)
+    }
+
+    /// a matcher with `global` in place of the global gitignore of the user
+    fn with_global(global: Gitignore) -> Self {
+        Self {
+            global: Arc::new(global),
+            parsed: Arc::new(Mutex::new(HashMap::new())),
+            repositories: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// whether `path` itself is ignored, the folders above it being checked already as in a
+    /// walk of the tree
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        let folders: Vec<&Path> = path.ancestors().skip(1).collect();
+        let repository = folders
+            .iter()
+            .position(|dir| self.is_repository(dir))
+            .map_or(folders.len(), |position| position + 1);
+        let lookups = [
+            (IGNORE_FILE, &folders[..]),
+            (GIT_IGNORE_FILE, &folders[..repository]),
+            (GIT_EXCLUDE_FILE, &folders[..repository]),
+        ];
+        for (name, folders) in lookups {
+            for dir in folders {
+                let Some(matcher) = self.parse(dir, name) else {
+                    continue;
+                };
+                match matcher.matched(path, is_dir) {
+                    Match::None => {}
+                    found => return found.is_ignore(),
+                }
+            }
+        }
+        self.global.matched(path, is_dir).is_ignore()
+    }
+
+    /// whether `path` or one of its folders below `root` is ignored, for a single path that
+    /// was not reached by walking `root`
+    pub fn is_ignored(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return true;
+        };
+        let mut current = root.to_path_buf();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            current.push(component);
+            let is_last = components.peek().is_none();
+            if self.matches(&current, !is_last || is_dir) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn is_repository(&self, dir: &Path) -> bool {
+        *self
+            .repositories
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| dir.join(".git").exists())
+    }
+
+    /// the ignore file `name` of `dir`, whose patterns are relative to `dir`
+    fn parse(&self, dir: &Path, name: &str) -> Option<Arc<Gitignore>> {
+        let path = dir.join(name);
+        let mut parsed = self
+            .parsed
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        parsed
+            .entry(path.clone())
+            .or_insert_with(|| {
+                if !path.is_file() {
+                    return None;
+                }
+                let mut builder = GitignoreBuilder::new(dir);
+                if let Some(e) = builder.add(&path) {
+                    warn!("{:?} is partly ignored: {}", path, e);
+                }
+                builder
+                    .build()
+                    .map_err(|e| warn!("{:?} is ignored: {}", path, e))
+                    .ok()
+                    .map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+impl Default for IgnoreMatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use search_engine::utils::generate_uid;
+
+    use super::*;
+
+    /// a folder of its own under the temporary directory, removed by the caller
+    fn test_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("rustindexer-test-{}", generate_uid()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, lines: &[&str]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, lines.join("\n")).unwrap();
+    }
+
+    fn global(root: &Path, lines: &[&str]) -> Gitignore {
+        let mut builder = GitignoreBuilder::new(root);
+        for line in lines {
+            builder.add_line(None, line).unwrap();
+        }
+        builder.build().unwrap()
+    }
+
+    fn no_global(root: &Path) -> IgnoreMatcher {
+        IgnoreMatcher::with_global(global(root, &[]))
+    }
+
+    #[test]
+    fn ignore_files_take_precedence_over_each_other() {
+        let root = test_dir();
+        fs::create_dir(root.join(".git")).unwrap();
+        write(&root.join(GIT_EXCLUDE_FILE), &["!*.b", "*.e"]);
+        write(
+            &root.join(GIT_IGNORE_FILE),
+            &["!*.c", "!*.e", "*.f", "!*.h"],
+        );
+        write(&root.join(IGNORE_FILE), &["!*.d", "!*.f", "*.h"]);
+        let matcher = IgnoreMatcher::with_global(global(&root, &["*.a", "*.b", "*.c", "*.d"]));
+        for (extension, ignored) in [
+            // only the global gitignore
+            ("a", true),
+            // info/exclude over the global gitignore
+            ("b", false),
+            // .gitignore over the global gitignore and info/exclude
+            ("c", false),
+            ("e", false),
+            // .ignore over every other file
+            ("d", false),
+            ("f", false),
+            ("h", true),
+            ("g", false),
+        ] {
+            let path = root.join(format!("file.{}", extension));
+            assert_eq!(matcher.matches(&path, false), ignored, "{:?}", path);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn negations_and_deeper_folders_bring_files_back() {
+        let root = test_dir();
+        write(&root.join(GIT_IGNORE_FILE), &["*.txt", "!important.txt"]);
+        write(&root.join("sub").join(GIT_IGNORE_FILE), &["!keep.txt"]);
+        let matcher = no_global(&root);
+        assert!(matcher.matches(&root.join("notes.txt"), false));
+        assert!(!matcher.matches(&root.join("important.txt"), false));
+        assert!(!matcher.matches(&root.join("sub").join("keep.txt"), false));
+        assert!(matcher.matches(&root.join("sub").join("other.txt"), false));
+        assert!(!matcher.matches(&root.join("notes.md"), false));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn gitignore_files_stop_at_the_repository() {
+        let root = test_dir();
+        let repository = root.join("repository");
+        fs::create_dir_all(repository.join(".git")).unwrap();
+        write(&root.join(GIT_IGNORE_FILE), &["*.txt"]);
+        write(&root.join(IGNORE_FILE), &["*.md"]);
+        let matcher = no_global(&root);
+        assert!(matcher.matches(&root.join("a.txt"), false));
+        assert!(!matcher.matches(&repository.join("a.txt"), false));
+        assert!(matcher.matches(&repository.join("a.md"), false));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn single_paths_are_checked_with_their_folders() {
+        let root = test_dir();
+        write(&root.join(GIT_IGNORE_FILE), &["build/"]);
+        let matcher = no_global(&root);
+        let output = root.join("build").join("out.txt");
+        // during a walk the folder would have been left out already
+        assert!(!matcher.matches(&output, false));
+        assert!(matcher.is_ignored(&root, &output, false));
+        assert!(matcher.is_ignored(&root, &root.join("build"), true));
+        assert!(!matcher.is_ignored(&root, &root.join("src").join("main.rs"), false));
+        assert!(matcher.is_ignored(&root, Path::new("/elsewhere/a.txt"), false));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
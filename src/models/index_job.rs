@@ -0,0 +1,971 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use log::{debug, warn};
+use search_engine::{index::Index, utils::generate_uid};
+
+use crate::config::Settings;
+use crate::models::archive::{entry_path, is_under, visit_entries, with_temp_copy, ArchiveKind};
+use crate::models::extract::{Extraction, ExtractorRegistry};
+use crate::models::ignore_files::IgnoreMatcher;
+use crate::models::index_model::StoredIndexModel;
+use crate::models::index_rules::IndexRules;
+use crate::models::metadata::{content_hash, FileMetadata, IndexMetadata};
+
+/// What the indexing thread sends back to the main loop while it works
+pub enum IndexProgress {
+    /// number of files found under the root folder
+    Discovered(usize),
+    Processing(ProgressSnapshot),
+    /// sent by the caller of run_index_job once the index has been stored
+    Finished(StoredIndexModel, IndexSummary),
+    Cancelled,
+}
+
+/// Handle shared between the main loop and the indexing thread. The thread checks it between
+/// two files, so cancelling or pausing takes effect once the current file is indexed.
+#[derive(Clone, Default)]
+pub struct IndexJobHandle {
+    cancelled: Arc<AtomicBool>,
+    paused: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl IndexJobHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // a paused thread must wake up to notice it has been cancelled
+        self.resume();
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+    pub fn pause(&self) {
+        let (lock, _) = &*self.paused;
+        if let Ok(mut paused) = lock.lock() {
+            *paused = true;
+        }
+    }
+    pub fn resume(&self) {
+        let (lock, condvar) = &*self.paused;
+        if let Ok(mut paused) = lock.lock() {
+            *paused = false;
+            condvar.notify_all();
+        }
+    }
+    pub fn is_paused(&self) -> bool {
+        let (lock, _) = &*self.paused;
+        lock.lock().map(|paused| *paused).unwrap_or(false)
+    }
+    /// blocks the indexing thread while the job is paused and tells whether it may go on
+    fn checkpoint(&self) -> bool {
+        let (lock, condvar) = &*self.paused;
+        if let Ok(mut paused) = lock.lock() {
+            while *paused {
+                match condvar.wait(paused) {
+                    Ok(guard) => paused = guard,
+                    Err(_) => break,
+                }
+            }
+        }
+        !self.is_cancelled()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ProgressSnapshot {
+    pub current_file: PathBuf,
+    pub processed: usize,
+    pub total: usize,
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl ProgressSnapshot {
+    pub fn fraction(&self) -> f64 {
+        if self.total_bytes > 0 {
+            self.bytes_read as f64 / self.total_bytes as f64
+        } else if self.total > 0 {
+            self.processed as f64 / self.total as f64
+        } else {
+            0.0
+        }
+    }
+    /// estimated remaining time, extrapolated from the throughput observed so far
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        if fraction <= 0.0 {
+            return None;
+        }
+        let total = self.elapsed.as_secs_f64() / fraction;
+        Some(Duration::from_secs_f64(
+            (total - self.elapsed.as_secs_f64()).max(0.0),
+        ))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IndexSummary {
+    pub root: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// None when the folder was indexed from scratch
+    pub changes: Option<IndexChanges>,
+    /// binary files no extractor reads, and documents that could not be read
+    pub skipped: usize,
+}
+
+/// what an incremental run changed compared to the previous index of the folder
+#[derive(Clone, Debug, Default)]
+pub struct IndexChanges {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+impl IndexChanges {
+    /// counts `path` as updated if the `previous` index had it, as added otherwise
+    fn count(&mut self, previous: Option<&StoredIndexModel>, path: &Path) {
+        match previous {
+            Some(previous) if previous.data.index.contains_key(path) => self.updated += 1,
+            _ => self.added += 1,
+        }
+    }
+}
+
+impl fmt::Display for IndexChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} removed",
+            self.added, self.updated, self.removed
+        )
+    }
+}
+
+/// decides which files under the root folder are indexed, built from the IndexRules of the
+/// index
+#[derive(Clone)]
+pub struct IndexFilter {
+    included: Option<GlobSet>,
+    excluded: GlobSet,
+    /// None when the ignore files are not respected
+    ignore_files: Option<IgnoreMatcher>,
+    include_hidden: bool,
+    max_file_size: u64,
+    max_depth: usize,
+    descend_archives: bool,
+}
+
+impl IndexFilter {
+    pub fn new(rules: &IndexRules) -> Self {
+        let included = (!rules.include.is_empty()).then(|| build_globs(&rules.include, "include"));
+        Self {
+            included,
+            excluded: build_globs(&rules.exclude, "exclude"),
+            ignore_files: rules.respect_ignore_files.then(IgnoreMatcher::new),
+            include_hidden: rules.include_hidden,
+            max_file_size: rules.max_file_size,
+            max_depth: rules.max_depth,
+            descend_archives: rules.descend_archives,
+        }
+    }
+    /// the filter of a folder that was never indexed
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(&IndexRules::from_settings(settings))
+    }
+    /// every file under `root` not left out by the ignore files, the hidden-files and depth
+    /// rules or an excluded folder. Size and globs are checked afterwards with accepts().
+    pub fn walk(&self, root: &str) -> Vec<String> {
+        let mut builder = WalkBuilder::new(root);
+        // the ignore files are read by our matcher, the watcher checks single paths with it
+        builder
+            .standard_filters(false)
+            .hidden(!self.include_hidden)
+            .max_depth((self.max_depth > 0).then_some(self.max_depth))
+            .sort_by_file_name(|a, b| a.cmp(b));
+        let (excluded, ignore_files) = (self.excluded.clone(), self.ignore_files.clone());
+        builder.filter_entry(move |entry| {
+            let is_dir = entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir());
+            if is_dir && excluded.is_match(entry.path().join("*")) {
+                return false;
+            }
+            // the root itself is always walked
+            entry.depth() == 0
+                || !ignore_files
+                    .as_ref()
+                    .is_some_and(|ignore_files| ignore_files.matches(entry.path(), is_dir))
+        });
+        builder
+            .build()
+            .filter_map(|entry| {
+                entry
+                    .map_err(|e| warn!("skipping an entry of {}: {}", root, e))
+                    .ok()
+            })
+            .filter(|entry| {
+                entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file())
+            })
+            .map(|entry| entry.path().to_string_lossy().to_string())
+            .collect()
+    }
+    pub fn accepts(&self, file: &str, size: u64) -> bool {
+        let too_big = self.max_file_size > 0 && size > self.max_file_size;
+        let included = self
+            .included
+            .as_ref()
+            .map_or(true, |included| included.is_match(file));
+        !too_big && included && !self.excluded.is_match(file)
+    }
+    /// whether the entries of the archive `file` are indexed. Only the exclude patterns apply to
+    /// the archive itself, the other rules are checked on its entries.
+    pub fn descends_into(&self, file: &Path) -> bool {
+        self.descend_archives && ArchiveKind::of(file).is_some() && !self.excluded.is_match(file)
+    }
+    /// true when nothing inside `dir` can be indexed, e.g. `**/.git/**` for a .git folder
+    pub fn skips_dir(&self, dir: &Path) -> bool {
+        self.excluded.is_match(dir.join("*"))
+    }
+    /// whether walk() would leave `path` out: a hidden or too deep path, or one the ignore
+    /// files leave out. Used for the single files seen by the watcher.
+    pub fn is_ignored(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return true;
+        };
+        let depth = relative.components().count();
+        if self.max_depth > 0 && depth > self.max_depth {
+            return true;
+        }
+        let hidden = relative
+            .components()
+            .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+        if hidden && !self.include_hidden {
+            return true;
+        }
+        self.ignore_files
+            .as_ref()
+            .is_some_and(|ignore_files| ignore_files.is_ignored(root, path, is_dir))
+    }
+}
+
+fn build_globs(patterns: &[String], kind: &str) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("ignoring the invalid {} pattern {}: {}", kind, pattern, e),
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+/// walks `root`, indexes every file one at a time and calls `report` after each step.
+/// With the `previous` index of the folder only new and changed files are tokenized again,
+/// the others are copied over and the deleted ones are left out.
+/// The callback keeps this function free of any gtk type so it can run on a worker thread.
+/// Returns None when the job was cancelled through `handle`.
+pub fn run_index_job<F>(
+    root: &str,
+    filter: &IndexFilter,
+    previous: Option<&StoredIndexModel>,
+    handle: &IndexJobHandle,
+    report: F,
+) -> Option<(Index, IndexMetadata, IndexSummary)>
+where
+    F: Fn(IndexProgress),
+{
+    let start = Instant::now();
+    let (list_files, files_metadata): (Vec<String>, Vec<Option<FileMetadata>>) = filter
+        .walk(root)
+        .into_iter()
+        .map(|file| {
+            let metadata = FileMetadata::read(Path::new(&file));
+            (file, metadata)
+        })
+        .filter(|(file, metadata)| {
+            filter.accepts(file, metadata.as_ref().map_or(0, |meta| meta.size))
+                || filter.descends_into(Path::new(file))
+        })
+        .unzip();
+    report(IndexProgress::Discovered(list_files.len()));
+    if !handle.checkpoint() {
+        return None;
+    }
+
+    let total_bytes = files_metadata.iter().flatten().map(|meta| meta.size).sum();
+    let total = list_files.len();
+    let mut index = Index::new(list_files.clone());
+    let mut metadata = IndexMetadata::default();
+    let mut changes = IndexChanges::default();
+    let mut skipped = 0;
+    let mut bytes_read = 0;
+    let extractors = ExtractorRegistry::builtin();
+
+    for (processed, (file, file_metadata)) in list_files.iter().zip(files_metadata).enumerate() {
+        if !handle.checkpoint() {
+            return None;
+        }
+        report(IndexProgress::Processing(ProgressSnapshot {
+            current_file: PathBuf::from(file),
+            processed,
+            total,
+            bytes_read,
+            total_bytes,
+            elapsed: start.elapsed(),
+        }));
+        let path = Path::new(file);
+        if filter.descends_into(path) {
+            let reused = previous
+                .zip(file_metadata.as_ref())
+                .and_then(|(previous, current)| {
+                    reuse_archive(&mut index, &mut metadata, previous, path, current, filter)
+                });
+            let archive_metadata = match reused {
+                Some((reused, entries)) => {
+                    changes.unchanged += entries;
+                    Some(reused)
+                }
+                None => {
+                    let modified = file_metadata.as_ref().map_or(0, |meta| meta.modified);
+                    let (entries, entries_skipped) = index_archive(
+                        &mut index,
+                        &mut metadata,
+                        path,
+                        modified,
+                        filter,
+                        &extractors,
+                    );
+                    skipped += entries_skipped;
+                    for entry in entries {
+                        changes.count(previous, &entry);
+                    }
+                    file_metadata.map(|file_metadata| file_metadata.with_hash(path))
+                }
+            };
+            // kept to notice when the archive changes, the archive itself is not a document
+            if let Some(archive_metadata) = archive_metadata {
+                bytes_read += archive_metadata.size;
+                metadata.insert(path, archive_metadata);
+            }
+            continue;
+        }
+        let reused = previous
+            .and_then(|previous| reuse_entry(&mut index, previous, path, file_metadata.as_ref()?));
+        let file_metadata = match reused {
+            Some(reused) => {
+                changes.unchanged += 1;
+                Some(reused)
+            }
+            None => match index_document(&mut index, path, &extractors) {
+                Ok(()) => {
+                    changes.count(previous, path);
+                    file_metadata.map(|file_metadata| file_metadata.with_hash(path))
+                }
+                Err(reason) => {
+                    debug!("{} is skipped: {}", file, reason);
+                    skipped += 1;
+                    None
+                }
+            },
+        };
+        if let Some(file_metadata) = file_metadata {
+            bytes_read += file_metadata.size;
+            metadata.insert(path, file_metadata);
+        }
+    }
+    changes.removed = previous.map_or(0, |previous| {
+        previous
+            .data
+            .index
+            .keys()
+            .filter(|path| !index.index.contains_key(*path))
+            .count()
+    });
+
+    let summary = IndexSummary {
+        root: root.to_string(),
+        files: total,
+        bytes: bytes_read,
+        elapsed: start.elapsed(),
+        changes: previous.map(|_| changes),
+        skipped,
+    };
+    Some((index, metadata, summary))
+}
+
+/// Applies the files created, changed or deleted under the root of `model` since it was built,
+/// as queued by the watcher. A path that is no longer an indexable file is removed, with
+/// everything indexed under it if it was a folder.
+pub fn apply_changes(
+    model: &StoredIndexModel,
+    root: &Path,
+    paths: &[PathBuf],
+    filter: &IndexFilter,
+) -> (Index, IndexMetadata, IndexChanges) {
+    let mut index = (*model.data).clone();
+    let mut metadata = (*model.metadata).clone();
+    let mut changes = IndexChanges::default();
+    let extractors = ExtractorRegistry::builtin();
+    for path in paths {
+        if path.is_file() && filter.descends_into(path) && !filter.is_ignored(root, path, false) {
+            // an archive changed: its entries are indexed again from scratch
+            let previous_entries = remove_under(&mut index, &mut metadata, path);
+            let file_metadata = FileMetadata::read(path);
+            let modified = file_metadata.as_ref().map_or(0, |meta| meta.modified);
+            let (entries, _) = index_archive(
+                &mut index,
+                &mut metadata,
+                path,
+                modified,
+                filter,
+                &extractors,
+            );
+            for entry in &entries {
+                if previous_entries.contains(entry) {
+                    changes.updated += 1;
+                } else {
+                    changes.added += 1;
+                }
+            }
+            changes.removed += previous_entries
+                .iter()
+                .filter(|entry| !entries.contains(entry))
+                .count();
+            if let Some(file_metadata) = file_metadata {
+                metadata.insert(path, file_metadata.with_hash(path));
+            }
+            continue;
+        }
+        let file_metadata = FileMetadata::read(path).filter(|file_metadata| {
+            path.is_file()
+                && !filter.is_ignored(root, path, false)
+                && filter.accepts(&path.to_string_lossy(), file_metadata.size)
+        });
+        let was_indexed = index.index.contains_key(path);
+        let indexed = file_metadata.map(|file_metadata| {
+            let result = index_document(&mut index, path, &extractors);
+            (file_metadata, result)
+        });
+        match indexed {
+            Some((file_metadata, Ok(()))) => {
+                if was_indexed {
+                    changes.updated += 1;
+                } else {
+                    changes.added += 1;
+                }
+                metadata.insert(path, file_metadata.with_hash(path));
+            }
+            Some((_, Err(reason))) => {
+                debug!("{:?} is skipped: {}", path, reason);
+                if index.index.remove(path).is_some() {
+                    metadata.files.remove(path);
+                    changes.removed += 1;
+                }
+            }
+            None => changes.removed += remove_under(&mut index, &mut metadata, path).len(),
+        }
+    }
+    (index, metadata, changes)
+}
+
+/// removes the documents under `path`, a file, a folder or an archive, with their metadata.
+/// Returns the paths of the documents removed.
+fn remove_under(index: &mut Index, metadata: &mut IndexMetadata, path: &Path) -> Vec<PathBuf> {
+    let removed: Vec<PathBuf> = index
+        .index
+        .keys()
+        .filter(|key| is_under(key, path))
+        .cloned()
+        .collect();
+    for key in &removed {
+        index.index.remove(key);
+    }
+    metadata.files.retain(|key, _| !is_under(key, path));
+    removed
+}
+
+/// the metadata to keep for `path` if the file did not change since `known` was recorded:
+/// same size and date, or same content when only the date changed
+fn unchanged_metadata(
+    known: &FileMetadata,
+    current: &FileMetadata,
+    path: &Path,
+) -> Option<FileMetadata> {
+    if known.size == current.size && known.modified == current.modified {
+        return Some(known.clone());
+    }
+    let hash = known.hash?;
+    if known.size != current.size || content_hash(path).ok()? != hash {
+        return None;
+    }
+    Some(FileMetadata {
+        hash: Some(hash),
+        ..current.clone()
+    })
+}
+
+/// Copies the entry of `path` from the previous index when the file did not change. Returns
+/// the metadata to keep.
+fn reuse_entry(
+    index: &mut Index,
+    previous: &StoredIndexModel,
+    path: &Path,
+    current: &FileMetadata,
+) -> Option<FileMetadata> {
+    let entry = previous.data.index.get(path)?;
+    let reused = unchanged_metadata(previous.metadata.files.get(path)?, current, path)?;
+    index.index.insert(path.to_path_buf(), entry.clone());
+    Some(reused)
+}
+
+/// Copies the entries of `archive` still accepted by `filter` from the previous index when the
+/// archive did not change. Returns the metadata to keep for the archive and the number of
+/// entries copied.
+fn reuse_archive(
+    index: &mut Index,
+    metadata: &mut IndexMetadata,
+    previous: &StoredIndexModel,
+    archive: &Path,
+    current: &FileMetadata,
+    filter: &IndexFilter,
+) -> Option<(FileMetadata, usize)> {
+    let reused = unchanged_metadata(previous.metadata.files.get(archive)?, current, archive)?;
+    let mut entries = 0;
+    for (key, entry) in previous.data.index.iter() {
+        if !is_under(key, archive) {
+            continue;
+        }
+        // the rules may have changed since the entry was indexed
+        let entry_metadata = previous.metadata.files.get(key);
+        let size = entry_metadata.map_or(0, |entry_metadata| entry_metadata.size);
+        if !filter.accepts(&key.to_string_lossy(), size) {
+            continue;
+        }
+        index.index.insert(key.clone(), entry.clone());
+        if let Some(entry_metadata) = entry_metadata {
+            metadata.insert(key, entry_metadata.clone());
+        }
+        entries += 1;
+    }
+    Some((reused, entries))
+}
+
+/// indexes the entries of `archive` accepted by `filter` under their entry_path(), dated like
+/// the archive. Returns the paths indexed and the number of entries skipped.
+fn index_archive(
+    index: &mut Index,
+    metadata: &mut IndexMetadata,
+    archive: &Path,
+    modified: u64,
+    filter: &IndexFilter,
+    extractors: &ExtractorRegistry,
+) -> (Vec<PathBuf>, usize) {
+    let mut indexed = Vec::new();
+    let mut skipped = 0;
+    let result = visit_entries(
+        archive,
+        |name, size| filter.accepts(&entry_path(archive, name).to_string_lossy(), size),
+        |name, content| {
+            let path = entry_path(archive, name);
+            let size = content.len() as u64;
+            let result = with_temp_copy(name, &content, |copy| {
+                index_document_as(index, copy, &path, extractors)
+            })
+            .map_err(|e| e.to_string())
+            .and_then(|result| result);
+            match result {
+                Ok(()) => {
+                    let entry_metadata = FileMetadata {
+                        size,
+                        modified,
+                        hash: None,
+                    };
+                    metadata.insert(&path, entry_metadata);
+                    indexed.push(path);
+                }
+                Err(reason) => {
+                    debug!("{:?} is skipped: {}", path, reason);
+                    skipped += 1;
+                }
+            }
+        },
+    );
+    if let Err(e) = result {
+        warn!(
+            "the archive {:?} could not be read entirely: {}",
+            archive, e
+        );
+    }
+    (indexed, skipped)
+}
+
+/// indexes `path` with the text the extractors give for it. Returns why the file was left
+/// out: a binary file no extractor reads, or a document that could not be read.
+pub fn index_document(
+    index: &mut Index,
+    path: &Path,
+    extractors: &ExtractorRegistry,
+) -> Result<(), String> {
+    index_document_as(index, path, path, extractors)
+}
+
+/// indexes the file `path` under `key`, which differs for the entries copied out of an archive
+fn index_document_as(
+    index: &mut Index,
+    path: &Path,
+    key: &Path,
+    extractors: &ExtractorRegistry,
+) -> Result<(), String> {
+    match extractors.searchable_text(path) {
+        Extraction::Plain if path == key => {
+            index_file(index, &path.to_string_lossy());
+            Ok(())
+        }
+        Extraction::Plain => {
+            index_single(index, path, key);
+            Ok(())
+        }
+        Extraction::Text(text) => index_text(index, key, &text).map_err(|e| e.to_string()),
+        Extraction::Binary(mime) => Err(format!("no extractor for {}", mime)),
+        Extraction::Failed(e) => Err(e),
+    }
+}
+
+/// the back end only indexes files, so the extracted text goes through a temporary file
+/// whose entry is then stored under the path of the document
+fn index_text(index: &mut Index, path: &Path, text: &str) -> io::Result<()> {
+    let temp_file = env::temp_dir().join(format!("rustindexer-{}.txt", generate_uid()));
+    fs::write(&temp_file, text)?;
+    index_single(index, &temp_file, path);
+    if let Err(e) = fs::remove_file(&temp_file) {
+        warn!("{:?} could not be removed: {}", temp_file, e);
+    }
+    Ok(())
+}
+
+/// indexes `file` on its own and stores its entry under `key`
+fn index_single(index: &mut Index, file: &Path, key: &Path) {
+    let mut single = Index::new(vec![file.to_string_lossy().to_string()]);
+    single.index_all();
+    if let Some(entry) = single.index.into_values().next() {
+        index.index.insert(key.to_path_buf(), entry);
+    }
+}
+
+/// index_all() only works on a whole list, so each file gets its own small Index that is then
+/// merged into the main one. This is what lets us report progress file by file.
+pub fn index_file(index: &mut Index, file: &str) {
+    let mut single = Index::new(vec![file.to_string()]);
+    single.index_all();
+    index.index.extend(single.index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a term no document contains, added to the entries of a previous index to tell the
+    /// entries copied from it from the ones indexed again
+    const MARKER: &str = "copiedfromthepreviousindex";
+
+    /// a folder of its own under the temporary directory, removed by the caller
+    fn test_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("rustindexer-test-{}", generate_uid()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// the default rules without the ignore files of the machine running the tests
+    fn filter() -> IndexFilter {
+        IndexFilter::new(&IndexRules {
+            respect_ignore_files: false,
+            ..IndexRules::default()
+        })
+    }
+
+    fn index(
+        root: &Path,
+        previous: Option<&StoredIndexModel>,
+    ) -> (Index, IndexMetadata, IndexSummary) {
+        let handle = IndexJobHandle::new();
+        run_index_job(
+            &root.to_string_lossy(),
+            &filter(),
+            previous,
+            &handle,
+            |_| {},
+        )
+        .unwrap()
+    }
+
+    /// the index of `root` as a previous index, every entry marked
+    fn previous_index(root: &Path) -> StoredIndexModel {
+        let (mut index, metadata, _) = index(root, None);
+        for entry in index.index.values_mut() {
+            entry.insert(MARKER.to_string(), 1);
+        }
+        StoredIndexModel::from_index(index, metadata, "test")
+    }
+
+    fn names(index: &Index) -> Vec<String> {
+        let mut names: Vec<String> = index
+            .index
+            .keys()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn is_copied(index: &Index, path: &Path) -> bool {
+        index.index[path].contains_key(MARKER)
+    }
+
+    /// changes b.txt, adds d.txt and deletes c.txt
+    fn change_files(root: &Path) {
+        fs::write(root.join("b.txt"), "beta changed").unwrap();
+        fs::write(root.join("d.txt"), "delta").unwrap();
+        fs::remove_file(root.join("c.txt")).unwrap();
+    }
+
+    fn write_files(root: &Path) {
+        for (name, text) in [("a.txt", "alpha"), ("b.txt", "beta"), ("c.txt", "gamma")] {
+            fs::write(root.join(name), text).unwrap();
+        }
+    }
+
+    #[test]
+    fn first_run_indexes_every_file() {
+        let root = test_dir();
+        write_files(&root);
+        let (index, metadata, summary) = index(&root, None);
+        assert_eq!(names(&index), ["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.bytes, 14);
+        assert!(summary.changes.is_none());
+        let a = metadata.files.get(&root.join("a.txt")).unwrap();
+        assert_eq!(a.size, 5);
+        assert!(a.hash.is_some());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn incremental_run_only_indexes_the_changed_files() {
+        let root = test_dir();
+        write_files(&root);
+        let previous = previous_index(&root);
+        change_files(&root);
+
+        let (index, metadata, summary) = index(&root, Some(&previous));
+        assert_eq!(names(&index), ["a.txt", "b.txt", "d.txt"]);
+        assert_eq!(summary.files, 3);
+        let changes = summary.changes.unwrap();
+        assert_eq!(changes.to_string(), "1 added, 1 updated, 1 removed");
+        assert_eq!(changes.unchanged, 1);
+        assert!(is_copied(&index, &root.join("a.txt")));
+        assert!(!is_copied(&index, &root.join("b.txt")));
+        assert!(!is_copied(&index, &root.join("d.txt")));
+        assert!(!metadata.files.contains_key(&root.join("c.txt")));
+        assert_eq!(metadata.files[&root.join("b.txt")].size, 12);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn entries_are_reused_when_only_the_date_changed() {
+        let root = test_dir();
+        let path = root.join("a.txt");
+        fs::write(&path, "alpha").unwrap();
+        let previous = previous_index(&root);
+        let current = FileMetadata::read(&path).unwrap();
+        let touched = FileMetadata {
+            modified: current.modified + 60,
+            ..current.clone()
+        };
+
+        // same content, the entry is copied with the new date
+        let mut index = Index::new(Vec::new());
+        let reused = reuse_entry(&mut index, &previous, &path, &touched).unwrap();
+        assert_eq!(reused.modified, touched.modified);
+        assert_eq!(reused.hash, previous.metadata.files[&path].hash);
+        assert!(is_copied(&index, &path));
+
+        // same size but another content
+        fs::write(&path, "alphb").unwrap();
+        let mut index = Index::new(Vec::new());
+        assert!(reuse_entry(&mut index, &previous, &path, &touched).is_none());
+        assert!(index.index.is_empty());
+
+        // without a hash a new date is enough to index the file again
+        let mut metadata = (*previous.metadata).clone();
+        metadata.files.get_mut(&path).unwrap().hash = None;
+        fs::write(&path, "alpha").unwrap();
+        let previous = StoredIndexModel::from_index((*previous.data).clone(), metadata, "test");
+        let mut index = Index::new(Vec::new());
+        assert!(reuse_entry(&mut index, &previous, &path, &touched).is_none());
+        assert!(reuse_entry(&mut index, &previous, &path, &current).is_some());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn watched_changes_are_applied_to_the_index() {
+        let root = test_dir();
+        write_files(&root);
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub").join("e.txt"), "epsilon").unwrap();
+        fs::write(root.join("sub").join("f.txt"), "phi").unwrap();
+        let model = previous_index(&root);
+        change_files(&root);
+
+        let paths = [root.join("b.txt"), root.join("c.txt"), root.join("d.txt")];
+        let (index, metadata, changes) = apply_changes(&model, &root, &paths, &filter());
+        assert_eq!(names(&index), ["a.txt", "b.txt", "d.txt", "e.txt", "f.txt"]);
+        assert_eq!(changes.to_string(), "1 added, 1 updated, 1 removed");
+        assert!(is_copied(&index, &root.join("a.txt")));
+        assert!(!is_copied(&index, &root.join("b.txt")));
+        assert!(metadata.files[&root.join("d.txt")].hash.is_some());
+
+        // a deleted folder takes everything indexed under it
+        fs::remove_dir_all(root.join("sub")).unwrap();
+        let model = StoredIndexModel::from_index(index, metadata, "test");
+        let (index, metadata, changes) =
+            apply_changes(&model, &root, &[root.join("sub")], &filter());
+        assert_eq!(names(&index), ["a.txt", "b.txt", "d.txt"]);
+        assert_eq!(changes.to_string(), "0 added, 0 updated, 2 removed");
+        assert!(!metadata.files.contains_key(&root.join("sub").join("e.txt")));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    fn rules() -> IndexRules {
+        IndexRules {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            respect_ignore_files: false,
+            include_hidden: false,
+            max_file_size: 0,
+            max_depth: 0,
+            descend_archives: true,
+        }
+    }
+
+    fn walked(root: &Path, rules: IndexRules) -> Vec<String> {
+        IndexFilter::new(&rules)
+            .walk(&root.to_string_lossy())
+            .iter()
+            .map(|file| {
+                let relative = Path::new(file).strip_prefix(root).unwrap();
+                relative.to_string_lossy().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn accepts_checks_the_size_and_the_globs() {
+        let filter = IndexFilter::new(&IndexRules {
+            include: vec![String::from("*.md")],
+            exclude: vec![String::from("**/drafts/**")],
+            max_file_size: 100,
+            ..rules()
+        });
+        assert!(filter.accepts("/docs/a.md", 100));
+        assert!(!filter.accepts("/docs/a.md", 101));
+        assert!(!filter.accepts("/docs/a.txt", 10));
+        assert!(!filter.accepts("/docs/drafts/a.md", 10));
+        // 0 is no limit and no include pattern takes every file
+        let filter = IndexFilter::new(&rules());
+        assert!(filter.accepts("/docs/a.txt", u64::MAX));
+    }
+
+    #[test]
+    fn archives_are_descended_into_unless_excluded() {
+        let archive = Path::new("/docs/backup.zip");
+        assert!(IndexFilter::new(&rules()).descends_into(archive));
+        assert!(IndexFilter::new(&rules()).descends_into(Path::new("/docs/a.tar.gz")));
+        assert!(!IndexFilter::new(&rules()).descends_into(Path::new("/docs/a.txt")));
+        let filter = IndexFilter::new(&IndexRules {
+            descend_archives: false,
+            ..rules()
+        });
+        assert!(!filter.descends_into(archive));
+        let filter = IndexFilter::new(&IndexRules {
+            exclude: vec![String::from("*.zip")],
+            ..rules()
+        });
+        assert!(!filter.descends_into(archive));
+        // the other rules apply to the entries, not to the archive
+        let filter = IndexFilter::new(&IndexRules {
+            include: vec![String::from("*.md")],
+            max_file_size: 1,
+            ..rules()
+        });
+        assert!(filter.descends_into(archive));
+    }
+
+    #[test]
+    fn hidden_files_and_depth_limit_the_walk() {
+        let root = test_dir();
+        for file in [
+            "a.txt",
+            ".hidden.txt",
+            ".dir/b.txt",
+            "sub/c.txt",
+            "sub/deep/d.txt",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "text").unwrap();
+        }
+        assert_eq!(
+            walked(&root, rules()),
+            ["a.txt", "sub/c.txt", "sub/deep/d.txt"]
+        );
+        let include_hidden = IndexRules {
+            include_hidden: true,
+            ..rules()
+        };
+        assert_eq!(
+            walked(&root, include_hidden),
+            [
+                ".dir/b.txt",
+                ".hidden.txt",
+                "a.txt",
+                "sub/c.txt",
+                "sub/deep/d.txt"
+            ]
+        );
+        let depth = |max_depth| IndexRules {
+            max_depth,
+            ..rules()
+        };
+        assert_eq!(walked(&root, depth(1)), ["a.txt"]);
+        assert_eq!(walked(&root, depth(2)), ["a.txt", "sub/c.txt"]);
+
+        // the watcher leaves out the same paths
+        let filter = IndexFilter::new(&depth(2));
+        assert!(filter.is_ignored(&root, &root.join(".hidden.txt"), false));
+        assert!(filter.is_ignored(&root, &root.join(".dir").join("b.txt"), false));
+        assert!(filter.is_ignored(&root, &root.join("sub/deep/d.txt"), false));
+        assert!(!filter.is_ignored(&root, &root.join("sub/c.txt"), false));
+        assert!(filter.is_ignored(&root, Path::new("/elsewhere/a.txt"), false));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
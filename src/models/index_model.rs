@@ -3,7 +3,17 @@ use search_engine::{
     types::{FromJson, IndexDoc, PseudoHash, TermFreq},
     utils::generate_uid,
 };
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::warn;
+
+use crate::config::DEFAULT_INDEX_PATH;
+use crate::models::catalog::{CatalogEntry, IndexCatalog};
+use crate::models::metadata::IndexMetadata;
 /// This struct is the representation of a file path and a dictionnary of term and frequencies for
 /// that file
 pub struct IndexModel<'a> {
@@ -24,14 +34,88 @@ impl<'a> IndexModel<'a> {
 #[derive(Clone)]
 pub struct StoredIndexModel {
     pub id: String,
-    pub data: Index,
+    /// human readable name shown in the ui, the indexed folder or the file it was loaded from
+    pub name: String,
+    /// shared with the search threads, an index is never modified once loaded
+    pub data: Arc<Index>,
+    /// size and modification time of the indexed files, used by the search filters
+    pub metadata: Arc<IndexMetadata>,
 }
 
 impl StoredIndexModel {
-    pub fn new() -> Self {
+    /// loads the most recently updated index of the catalog, or the legacy
+    /// data/_index-index.json when the catalog is still empty. Ok(None) means there is no
+    /// index yet (fresh install), an Err carries the reason a stored index could not be read.
+    pub fn load_last() -> Result<Option<Self>, String> {
+        let catalog = IndexCatalog::load();
+        if let Some(entry) = catalog.latest() {
+            return Self::open(entry).map(Some);
+        }
+        let legacy_path = Path::new(DEFAULT_INDEX_PATH);
+        if legacy_path.exists() {
+            return Self::from_file(legacy_path).map(Some);
+        }
+        Ok(None)
+    }
+    /// loads an index of the catalog, the model takes the id of the catalog entry
+    pub fn open(entry: &CatalogEntry) -> Result<Self, String> {
+        let path = IndexCatalog::index_path(&entry.id);
+        let data =
+            Index::from_json(path.to_string_lossy().as_ref()).map_err(|e| format!("{:?}", e))?;
+        Ok(Self {
+            id: entry.id.clone(),
+            name: entry.name.clone(),
+            data: Arc::new(data),
+            metadata: Arc::new(IndexMetadata::load(&path)),
+        })
+    }
+    /// the index stored in the catalog for `root`, None if the folder was never indexed or its
+    /// index can't be read
+    pub fn for_root(root: &Path) -> Option<Self> {
+        let catalog = IndexCatalog::load();
+        let entry = catalog.find_by_root(root)?;
+        Self::open(entry)
+            .map_err(|e| warn!("the previous index of {:?} is ignored: {}", root, e))
+            .ok()
+    }
+    /// loads any index file produced by the back end, the file name becomes the model name
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        if !path.is_file() {
+            return Err(format!("{} is not a file", path.to_string_lossy()));
+        }
+        let data = Index::from_json(path.to_string_lossy().as_ref()).map_err(|e| {
+            format!(
+                "{} is not a valid index file: {:?}",
+                path.to_string_lossy(),
+                e
+            )
+        })?;
+        Ok(Self {
+            id: generate_uid(),
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string_lossy().to_string()),
+            data: Arc::new(data),
+            metadata: Arc::new(IndexMetadata::load(path)),
+        })
+    }
+    /// wraps an index that was just built and stored in the catalog
+    pub fn from_entry(data: Index, metadata: IndexMetadata, entry: &CatalogEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            name: entry.name.clone(),
+            data: Arc::new(data),
+            metadata: Arc::new(metadata),
+        }
+    }
+    /// wraps an index that was just built in memory
+    pub fn from_index(data: Index, metadata: IndexMetadata, name: &str) -> Self {
         Self {
             id: generate_uid(),
-            data: Index::from_json("data/_index-index.json").unwrap(),
+            name: name.to_string(),
+            data: Arc::new(data),
+            metadata: Arc::new(metadata),
         }
     }
 
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Settings;
+
+/// What gets indexed under the root folder of an index. The rules are kept in the catalog
+/// entry so rebuilding or watching the folder walks it the same way.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexRules {
+    /// globs a file must match to be indexed, every file when empty
+    pub include: Vec<String>,
+    /// globs of the files and folders left out, checked after `include`
+    pub exclude: Vec<String>,
+    /// honor the .gitignore and .ignore files found in the folder
+    pub respect_ignore_files: bool,
+    /// index the files and folders whose name starts with a dot
+    pub include_hidden: bool,
+    /// bytes, 0 disables the limit
+    pub max_file_size: u64,
+    /// how many folders deep files are indexed, 1 for the root folder only, 0 for no limit
+    pub max_depth: usize,
+    /// index the files inside zip and tar archives
+    pub descend_archives: bool,
+}
+
+impl Default for IndexRules {
+    fn default() -> Self {
+        Self::from_settings(&Settings::default())
+    }
+}
+
+impl IndexRules {
+    /// the rules proposed for a folder that was never indexed
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: settings.excluded_patterns.clone(),
+            respect_ignore_files: true,
+            include_hidden: false,
+            max_file_size: settings.max_file_size,
+            max_depth: 0,
+            descend_archives: true,
+        }
+    }
+}
+
+/// splits a comma separated list of globs as typed in the forms, empty items are dropped
+pub fn parse_patterns(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_are_split_on_commas() {
+        assert_eq!(
+            parse_patterns(" *.log, target/ ,,**/.git/** "),
+            ["*.log", "target/", "**/.git/**"]
+        );
+        assert!(parse_patterns("").is_empty());
+        assert!(parse_patterns(" , ,").is_empty());
+    }
+
+    #[test]
+    fn missing_rules_take_their_default() {
+        let rules: IndexRules = serde_json::from_str(r#"{"include": ["*.md"]}"#).unwrap();
+        assert_eq!(
+            rules,
+            IndexRules {
+                include: vec![String::from("*.md")],
+                ..IndexRules::default()
+            }
+        );
+        assert_eq!(rules.exclude, Settings::default().excluded_patterns);
+        assert_eq!(rules.max_depth, 0);
+    }
+}
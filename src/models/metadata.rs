@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::types::StandardResult;
+use crate::utils::write_json_atomic;
+
+/// what we know about an indexed file besides its terms, captured while indexing
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileMetadata {
+    pub size: u64,
+    /// unix timestamp in seconds
+    pub modified: u64,
+    /// content_hash() of the file, None for files indexed before it was recorded
+    #[serde(default)]
+    pub hash: Option<u64>,
+}
+
+impl FileMetadata {
+    pub fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Some(Self {
+            size: metadata.len(),
+            modified,
+            hash: None,
+        })
+    }
+    /// the same metadata with the content hash of `path`, left empty if it can't be read
+    pub fn with_hash(mut self, path: &Path) -> Self {
+        self.hash = content_hash(path).ok();
+        self
+    }
+}
+
+/// 64 bits FNV-1a hash of the content of `path`. Not meant to resist collisions on purpose,
+/// only to notice that a file whose date changed still has the same content.
+pub fn content_hash(path: &Path) -> io::Result<u64> {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut buffer = [0; 64 * 1024];
+    let mut hash = FNV_OFFSET;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hash);
+        }
+        for byte in &buffer[..read] {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// The metadata of every file of an index. The index json belongs to the back end, so this is
+/// stored in a sidecar file next to it, see sidecar_path().
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IndexMetadata {
+    pub files: HashMap<PathBuf, FileMetadata>,
+}
+
+impl IndexMetadata {
+    /// `index.json` => `index.meta.json`
+    pub fn sidecar_path(index_path: &Path) -> PathBuf {
+        index_path.with_extension("meta.json")
+    }
+    /// loads the sidecar of `index_path`, indexes built before it existed get an empty one
+    pub fn load(index_path: &Path) -> Self {
+        let path = Self::sidecar_path(index_path);
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                debug!("the metadata {:?} could not be parsed: {}", path, e);
+                Self::default()
+            }),
+            Err(e) => {
+                debug!("no metadata loaded from {:?}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+    pub fn save(&self, index_path: &Path) -> StandardResult {
+        write_json_atomic(self, &Self::sidecar_path(index_path))
+    }
+    pub fn insert(&mut self, path: &Path, metadata: FileMetadata) {
+        self.files.insert(path.to_path_buf(), metadata);
+    }
+    /// the metadata captured at index time, or read from the disk when the index has none
+    pub fn get(&self, path: &Path) -> Option<FileMetadata> {
+        self.files
+            .get(path)
+            .cloned()
+            .or_else(|| FileMetadata::read(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use search_engine::utils::generate_uid;
+
+    use super::*;
+
+    /// a folder of its own under the temporary directory, removed by the caller
+    fn test_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("rustindexer-test-{}", generate_uid()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn content_hash_follows_the_content() {
+        let dir = test_dir();
+        let (a, b, c) = (dir.join("a.txt"), dir.join("b.txt"), dir.join("c.txt"));
+        fs::write(&a, "same text").unwrap();
+        fs::write(&b, "same text").unwrap();
+        fs::write(&c, "other text").unwrap();
+        assert_eq!(content_hash(&a).unwrap(), content_hash(&b).unwrap());
+        assert_ne!(content_hash(&a).unwrap(), content_hash(&c).unwrap());
+        // the FNV-1a reference values
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "a").unwrap();
+        assert_eq!(content_hash(&a).unwrap(), 0xcbf29ce484222325);
+        assert_eq!(content_hash(&b).unwrap(), 0xaf63dc4c8601ec8c);
+        assert!(content_hash(&dir.join("missing.txt")).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn file_metadata_is_read_from_the_disk() {
+        let dir = test_dir();
+        let path = dir.join("a.txt");
+        fs::write(&path, "12345").unwrap();
+        let metadata = FileMetadata::read(&path).unwrap();
+        assert_eq!(metadata.size, 5);
+        assert!(metadata.modified > 0);
+        assert_eq!(metadata.hash, None);
+        let metadata = metadata.with_hash(&path);
+        assert_eq!(metadata.hash, Some(content_hash(&path).unwrap()));
+        assert!(FileMetadata::read(&dir.join("missing.txt")).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn index_metadata_is_stored_next_to_the_index() {
+        let dir = test_dir();
+        let index_path = dir.join("index.json");
+        assert_eq!(
+            IndexMetadata::sidecar_path(&index_path),
+            dir.join("index.meta.json")
+        );
+        assert!(IndexMetadata::load(&index_path).files.is_empty());
+
+        let document = dir.join("a.txt");
+        let mut metadata = IndexMetadata::default();
+        let file_metadata = FileMetadata {
+            size: 42,
+            modified: 1_700_000_000,
+            hash: Some(7),
+        };
+        metadata.insert(&document, file_metadata);
+        metadata.save(&index_path).unwrap();
+        let loaded = IndexMetadata::load(&index_path).get(&document).unwrap();
+        assert_eq!(
+            (loaded.size, loaded.modified, loaded.hash),
+            (42, 1_700_000_000, Some(7))
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn metadata_without_a_hash_still_loads() {
+        let dir = test_dir();
+        let index_path = dir.join("index.json");
+        let sidecar = r#"{"files": {"/docs/a.txt": {"size": 3, "modified": 10}}}"#;
+        fs::write(IndexMetadata::sidecar_path(&index_path), sidecar).unwrap();
+        let loaded = IndexMetadata::load(&index_path);
+        let file_metadata = loaded.get(Path::new("/docs/a.txt")).unwrap();
+        assert_eq!(file_metadata.size, 3);
+        assert_eq!(file_metadata.hash, None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn files_missing_from_the_index_are_read_from_the_disk() {
+        let dir = test_dir();
+        let path = dir.join("a.txt");
+        fs::write(&path, "abc").unwrap();
+        let metadata = IndexMetadata::default();
+        assert_eq!(metadata.get(&path).map(|meta| meta.size), Some(3));
+        assert!(metadata.get(&dir.join("missing.txt")).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
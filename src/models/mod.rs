@@ -1 +1,16 @@
+pub mod archive;
+pub mod catalog;
+pub mod document;
+pub mod export;
+pub mod extract;
+pub mod filter;
+pub mod history;
+pub mod ignore_files;
+pub mod index_job;
 pub mod index_model;
+pub mod index_rules;
+pub mod metadata;
+pub mod pagination;
+pub mod query;
+pub mod search_model;
+pub mod snippet;
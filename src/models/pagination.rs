@@ -0,0 +1,127 @@
+use std::ops::Range;
+
+use crate::utils::format_count;
+
+/// Which slice of the hits of a search is displayed. By default the list shows one page at a
+/// time, in load more mode the pages loaded so far stay in the list.
+#[derive(Clone, Debug, Default)]
+pub struct ResultPages {
+    /// number of matching documents, only the `kept` best ones can be displayed
+    pub total: usize,
+    pub kept: usize,
+    pub page_size: usize,
+    pub load_more: bool,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ResultPages {
+    pub fn new(total: usize, kept: usize, page_size: usize, load_more: bool) -> Self {
+        let page_size = page_size.max(1);
+        Self {
+            total,
+            kept,
+            page_size,
+            load_more,
+            start: 0,
+            end: page_size.min(kept),
+        }
+    }
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+    pub fn has_previous(&self) -> bool {
+        !self.load_more && self.start > 0
+    }
+    pub fn has_next(&self) -> bool {
+        self.end < self.kept
+    }
+    /// moves to the next page, or appends it in load more mode. Returns the range of hits
+    /// that were not displayed before.
+    pub fn next(&mut self) -> Range<usize> {
+        if !self.has_next() {
+            return self.end..self.end;
+        }
+        let end = (self.end + self.page_size).min(self.kept);
+        let added = self.end..end;
+        if !self.load_more {
+            self.start = self.end;
+        }
+        self.end = end;
+        added
+    }
+    pub fn previous(&mut self) -> Range<usize> {
+        if self.has_previous() {
+            self.end = self.start;
+            self.start = self.start.saturating_sub(self.page_size);
+        }
+        self.range()
+    }
+    /// "1–50 of 12 304"
+    pub fn label(&self) -> String {
+        if self.kept == 0 {
+            return String::from("no results");
+        }
+        let label = format!(
+            "{}–{} of {}",
+            format_count(self.start + 1),
+            format_count(self.end),
+            format_count(self.total)
+        );
+        if self.kept < self.total {
+            format!("{} (best {} kept)", label, format_count(self.kept))
+        } else {
+            label
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_move_forward_and_back() {
+        let mut pages = ResultPages::new(120, 120, 50, false);
+        assert_eq!(pages.range(), 0..50);
+        assert!(!pages.has_previous());
+        assert_eq!(pages.next(), 50..100);
+        assert_eq!(pages.range(), 50..100);
+        assert_eq!(pages.next(), 100..120);
+        assert!(!pages.has_next());
+        assert_eq!(pages.next(), 120..120);
+        assert_eq!(pages.previous(), 50..100);
+        assert_eq!(pages.previous(), 0..50);
+        assert_eq!(pages.previous(), 0..50);
+    }
+
+    #[test]
+    fn load_more_keeps_the_loaded_pages() {
+        let mut pages = ResultPages::new(120, 120, 50, true);
+        assert_eq!(pages.next(), 50..100);
+        assert_eq!(pages.range(), 0..100);
+        assert!(!pages.has_previous());
+        assert_eq!(pages.next(), 100..120);
+        assert_eq!(pages.range(), 0..120);
+    }
+
+    #[test]
+    fn fewer_hits_than_a_page() {
+        let pages = ResultPages::new(3, 3, 50, false);
+        assert_eq!(pages.range(), 0..3);
+        assert!(!pages.has_next());
+        assert_eq!(ResultPages::new(0, 0, 0, false).range(), 0..0);
+    }
+
+    #[test]
+    fn label_counts_the_hits() {
+        assert_eq!(ResultPages::new(0, 0, 50, false).label(), "no results");
+        let mut pages = ResultPages::new(12304, 12304, 50, false);
+        pages.next();
+        assert_eq!(pages.label(), "51–100 of 12 304");
+        assert_eq!(
+            ResultPages::new(12304, 1000, 50, false).label(),
+            "1–50 of 12 304 (best 1 000 kept)"
+        );
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use search_engine::{index::Index, types::TermFreq};
+
+use crate::models::document::DocumentReader;
+use crate::models::filter::Filter;
+use crate::models::snippet::{query_terms, words};
+
+/// A parsed search query. Adjacent terms are combined with AND, `-term` is a shortcut for
+/// `NOT term` and "quoted words" must appear next to each other in the document.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+    Term(String),
+    Phrase(Vec<String>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+/// a syntax error, `position` is the character offset in the query where it was found
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Minus,
+    Open,
+    Close,
+    Filter(Filter),
+}
+
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push((Token::Open, i));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((Token::Close, i));
+                i += 1;
+            }
+            '"' => {
+                let start = i;
+                let Some(length) = chars[i + 1..].iter().position(|c| *c == '"') else {
+                    return Err(ParseError {
+                        message: String::from("unclosed quote"),
+                        position: start,
+                    });
+                };
+                let phrase: String = chars[i + 1..i + 1 + length].iter().collect();
+                tokens.push((Token::Phrase(phrase), start));
+                i += length + 2;
+            }
+            '-' if i + 1 < chars.len() && !chars[i + 1].is_whitespace() => {
+                tokens.push((Token::Minus, i));
+                i += 1;
+            }
+            _ => {
+                let start = i;
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && !matches!(chars[i], '(' | ')' | '"')
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ if Filter::is_filter(&word) => {
+                        let mut filter = Filter::parse(&word).map_err(|message| ParseError {
+                            message,
+                            position: start,
+                        })?;
+                        filter.span = byte_offset(query, start)..byte_offset(query, i);
+                        Token::Filter(filter)
+                    }
+                    _ => Token::Word(word),
+                };
+                tokens.push((token, start));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// length of the query in characters, reported for errors at the very end
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map(|(_, offset)| *offset)
+            .unwrap_or(self.end)
+    }
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            message: message.to_string(),
+            position: self.offset(),
+        }
+    }
+    // or := and ("OR" and)*
+    fn parse_or(&mut self) -> Result<Query, ParseError> {
+        let mut operands = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            operands.push(self.parse_and()?);
+        }
+        Ok(flatten(operands, Query::Or))
+    }
+    // and := unary (["AND"] unary)*
+    fn parse_and(&mut self) -> Result<Query, ParseError> {
+        let mut operands = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.position += 1;
+                    operands.push(self.parse_unary()?);
+                }
+                Some(Token::Or) | Some(Token::Close) | None => break,
+                Some(_) => operands.push(self.parse_unary()?),
+            }
+        }
+        Ok(flatten(operands, Query::And))
+    }
+    // unary := ("NOT" | "-") unary | primary
+    fn parse_unary(&mut self) -> Result<Query, ParseError> {
+        match self.peek() {
+            Some(Token::Not) | Some(Token::Minus) => {
+                self.position += 1;
+                Ok(Query::Not(Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+    // primary := word | "phrase" | "(" or ")"
+    fn parse_primary(&mut self) -> Result<Query, ParseError> {
+        let token = self.peek().cloned();
+        match token {
+            Some(Token::Word(word)) => {
+                self.position += 1;
+                Ok(Query::Term(word))
+            }
+            Some(Token::Phrase(phrase)) => {
+                let phrase_terms = query_terms_in_order(&phrase);
+                if phrase_terms.is_empty() {
+                    return Err(self.error("empty phrase"));
+                }
+                self.position += 1;
+                Ok(Query::Phrase(phrase_terms))
+            }
+            Some(Token::Open) => {
+                self.position += 1;
+                let query = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(self.error("expected ')'"));
+                }
+                self.position += 1;
+                Ok(query)
+            }
+            Some(Token::Close) => Err(self.error("unexpected ')'")),
+            Some(Token::And) | Some(Token::Or) => {
+                Err(self.error("operator without a term before it"))
+            }
+            Some(Token::Not) | Some(Token::Minus) => unreachable!("handled by parse_unary"),
+            Some(Token::Filter(_)) => unreachable!("removed by take_filters"),
+            None => Err(self.error("expected a term")),
+        }
+    }
+}
+
+fn flatten(mut operands: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    if operands.len() == 1 {
+        operands.remove(0)
+    } else {
+        combine(operands)
+    }
+}
+
+fn query_terms_in_order(text: &str) -> Vec<String> {
+    words(text)
+        .into_iter()
+        .map(|range| text[range].to_lowercase())
+        .collect()
+}
+
+/// a query split in its terms and its filters, both optional
+#[derive(Clone, Debug, Default)]
+pub struct ParsedQuery {
+    pub query: Option<Query>,
+    pub filters: Vec<Filter>,
+}
+
+impl ParsedQuery {
+    pub fn is_empty(&self) -> bool {
+        self.query.is_none() && self.filters.is_empty()
+    }
+}
+
+/// the byte offset of the character at `offset` in `query`, its length past the end
+fn byte_offset(query: &str, offset: usize) -> usize {
+    query
+        .char_indices()
+        .nth(offset)
+        .map_or(query.len(), |(byte, _)| byte)
+}
+
+/// Filters apply to the whole result list wherever they are written, so they are pulled out
+/// of the tokens before parsing. A NOT or `-` right before a filter negates it.
+fn take_filters(query: &str, tokens: Vec<(Token, usize)>) -> (Vec<(Token, usize)>, Vec<Filter>) {
+    let mut remaining: Vec<(Token, usize)> = Vec::with_capacity(tokens.len());
+    let mut filters = Vec::new();
+    for (token, offset) in tokens {
+        match token {
+            Token::Filter(mut filter) => {
+                let negation = match remaining.last() {
+                    Some((Token::Not, offset)) | Some((Token::Minus, offset)) => Some(*offset),
+                    _ => None,
+                };
+                if let Some(negation) = negation {
+                    remaining.pop();
+                    filter.span.start = byte_offset(query, negation);
+                    filters.push(filter.negate());
+                } else {
+                    filters.push(filter);
+                }
+            }
+            token => remaining.push((token, offset)),
+        }
+    }
+    (remaining, filters)
+}
+
+/// parses a query, both parts of the result are empty for an empty query
+pub fn parse(query: &str) -> Result<ParsedQuery, ParseError> {
+    let (tokens, filters) = take_filters(query, tokenize(query)?);
+    if tokens.is_empty() {
+        return Ok(ParsedQuery {
+            query: None,
+            filters,
+        });
+    }
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        end: query.chars().count(),
+    };
+    let parsed = parser.parse_or()?;
+    if parser.position < parser.tokens.len() {
+        return Err(parser.error("unexpected ')'"));
+    }
+    Ok(ParsedQuery {
+        query: Some(parsed),
+        filters,
+    })
+}
+
+/// the terms worth highlighting in a document: every term that is not negated
+pub fn highlight_terms(query: &str) -> Vec<String> {
+    match parse(query).map(|parsed| parsed.query) {
+        Ok(Some(parsed)) => {
+            let mut terms = Vec::new();
+            parsed.collect_positive_terms(&mut terms);
+            terms.sort();
+            terms.dedup();
+            terms
+        }
+        _ => query_terms(query),
+    }
+}
+
+impl Query {
+    fn collect_positive_terms(&self, terms: &mut Vec<String>) {
+        match self {
+            Query::Term(term) => terms.extend(query_terms(term)),
+            Query::Phrase(words) => terms.extend(words.iter().cloned()),
+            Query::And(operands) | Query::Or(operands) => operands
+                .iter()
+                .for_each(|operand| operand.collect_positive_terms(terms)),
+            Query::Not(_) => {}
+        }
+    }
+
+    /// scores of the documents matching the query, the scores of the terms are summed.
+    /// Phrases are checked in the documents read through `reader`.
+    pub fn evaluate(&self, index: &Index, reader: &DocumentReader) -> HashMap<PathBuf, f32> {
+        match self {
+            // a term found in every document has an idf of 0, the documents containing it
+            // still match with a 0.0 score
+            Query::Term(term) => {
+                let scores = index.idf_calculation(term);
+                // a word like `foo-bar` is split in the same terms as the document
+                let words = query_terms(term);
+                index
+                    .index
+                    .iter()
+                    .filter(|(_, freq)| {
+                        !words.is_empty() && words.iter().all(|word| contains_term(freq, word))
+                    })
+                    .map(|(path, _)| (path.clone(), scores.get(path).copied().unwrap_or(0.0)))
+                    .collect()
+            }
+            Query::Phrase(words) => {
+                let all_words = Query::And(words.iter().cloned().map(Query::Term).collect());
+                let candidates = all_words.evaluate(index, reader);
+                reader.expect(candidates.keys().map(PathBuf::as_path));
+                candidates
+                    .into_iter()
+                    .filter(|(path, _)| contains_phrase(reader, path, words))
+                    .collect()
+            }
+            Query::Or(operands) => {
+                let mut scores = HashMap::new();
+                for operand in operands {
+                    for (path, score) in operand.evaluate(index, reader) {
+                        *scores.entry(path).or_insert(0.0) += score;
+                    }
+                }
+                scores
+            }
+            Query::And(operands) => {
+                let (negated, positive): (Vec<&Query>, Vec<&Query>) = operands
+                    .iter()
+                    .partition(|operand| matches!(operand, Query::Not(_)));
+                let mut scores = match positive.split_first() {
+                    Some((first, rest)) => {
+                        let mut scores = first.evaluate(index, reader);
+                        for operand in rest {
+                            let other = operand.evaluate(index, reader);
+                            scores.retain(|path, _| other.contains_key(path));
+                            for (path, score) in scores.iter_mut() {
+                                *score += other[path];
+                            }
+                        }
+                        scores
+                    }
+                    None => all_documents(index),
+                };
+                for operand in negated {
+                    if let Query::Not(excluded) = operand {
+                        for path in excluded.evaluate(index, reader).keys() {
+                            scores.remove(path);
+                        }
+                    }
+                }
+                scores
+            }
+            Query::Not(excluded) => {
+                let excluded: HashSet<PathBuf> =
+                    excluded.evaluate(index, reader).into_keys().collect();
+                all_documents(index)
+                    .into_iter()
+                    .filter(|(path, _)| !excluded.contains(path))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// every document of the index with a zero score, what a purely negative query starts from
+pub fn all_documents(index: &Index) -> HashMap<PathBuf, f32> {
+    index.index.keys().map(|path| (path.clone(), 0.0)).collect()
+}
+
+/// whether the term frequencies of a document list `term`, whatever the case the back end
+/// stored it with
+fn contains_term(freq: &TermFreq, term: &str) -> bool {
+    freq.contains_key(term)
+        || freq.contains_key(&term.to_uppercase())
+        || freq.contains_key(&term.to_lowercase())
+}
+
+fn contains_phrase(reader: &DocumentReader, path: &Path, phrase: &[String]) -> bool {
+    let Ok(text) = reader.load_text(path) else {
+        return false;
+    };
+    let document = query_terms_in_order(&text);
+    document
+        .windows(phrase.len())
+        .any(|window| window == phrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::filter::FilterKind;
+
+    fn term(word: &str) -> Query {
+        Query::Term(word.to_string())
+    }
+
+    fn parse_query(query: &str) -> Query {
+        parse(query).unwrap().query.unwrap()
+    }
+
+    #[test]
+    fn adjacent_terms_are_combined_with_and() {
+        assert_eq!(
+            parse_query("rust gtk"),
+            Query::And(vec![term("rust"), term("gtk")])
+        );
+        assert_eq!(
+            parse_query("rust AND gtk"),
+            Query::And(vec![term("rust"), term("gtk")])
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse_query("a b OR c"),
+            Query::Or(vec![Query::And(vec![term("a"), term("b")]), term("c")])
+        );
+        assert_eq!(
+            parse_query("a (b OR c)"),
+            Query::And(vec![term("a"), Query::Or(vec![term("b"), term("c")])])
+        );
+    }
+
+    #[test]
+    fn not_and_minus_negate_the_next_term() {
+        assert_eq!(
+            parse_query("a NOT b"),
+            Query::And(vec![term("a"), Query::Not(Box::new(term("b")))])
+        );
+        assert_eq!(
+            parse_query("a -b"),
+            Query::And(vec![term("a"), Query::Not(Box::new(term("b")))])
+        );
+    }
+
+    #[test]
+    fn phrases_keep_their_words_in_order() {
+        assert_eq!(
+            parse_query("\"Hello, World\""),
+            Query::Phrase(vec![String::from("hello"), String::from("world")])
+        );
+    }
+
+    #[test]
+    fn empty_query_parses_to_nothing() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn syntax_errors_report_their_position() {
+        for (query, position) in [
+            ("\"open", 0),
+            ("(a", 2),
+            ("a)", 1),
+            ("OR a", 0),
+            ("a AND", 5),
+            ("\"\"", 0),
+        ] {
+            let error = parse(query).unwrap_err();
+            assert_eq!(error.position, position, "{}: {}", query, error);
+        }
+    }
+
+    #[test]
+    fn filters_are_taken_out_of_the_query() {
+        let parsed = parse("report ext:pdf").unwrap();
+        assert_eq!(parsed.query, Some(term("report")));
+        assert_eq!(parsed.filters.len(), 1);
+        assert_eq!(
+            parsed.filters[0].kind,
+            FilterKind::Extension(String::from("pdf"))
+        );
+        assert!(!parsed.filters[0].negated);
+
+        let parsed = parse("ext:pdf").unwrap();
+        assert_eq!(parsed.query, None);
+        assert!(!parsed.is_empty());
+    }
+
+    #[test]
+    fn negated_filters_include_their_negation_in_their_span() {
+        let query = "a NOT ext:pdf b -path:tmp";
+        let parsed = parse(query).unwrap();
+        assert_eq!(parsed.query, Some(Query::And(vec![term("a"), term("b")])));
+        let spans: Vec<&str> = parsed
+            .filters
+            .iter()
+            .map(|filter| &query[filter.span.clone()])
+            .collect();
+        assert_eq!(spans, ["NOT ext:pdf", "-path:tmp"]);
+        assert!(parsed.filters.iter().all(|filter| filter.negated));
+    }
+
+    #[test]
+    fn filter_spans_are_byte_offsets() {
+        let query = "café ext:md";
+        let parsed = parse(query).unwrap();
+        assert_eq!(&query[parsed.filters[0].span.clone()], "ext:md");
+    }
+
+    #[test]
+    fn invalid_filters_are_errors() {
+        let error = parse("a size:big").unwrap_err();
+        assert_eq!(error.position, 2);
+    }
+
+    #[test]
+    fn highlight_terms_skip_negated_terms() {
+        assert_eq!(
+            highlight_terms("Rust -java \"gtk app\""),
+            ["app", "gtk", "rust"]
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use std::path::PathBuf;
+
+use log::debug;
+use search_engine::index::Index;
+use serde::Serialize;
+
+use crate::models::document::DocumentReader;
+use crate::models::metadata::{FileMetadata, IndexMetadata};
+use crate::models::query::{self, all_documents, highlight_terms, ParseError};
+use crate::models::snippet::{extract_snippets, Snippet};
+
+/// snippets extracted for each of the best hits
+const SNIPPETS_PER_HIT: usize = 3;
+
+/// one document matching a query with its tf-idf score
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchHit {
+    pub path: PathBuf,
+    pub score: f32,
+    /// size in bytes and modification time (unix seconds), None when they are unknown
+    pub size: Option<u64>,
+    pub modified: Option<u64>,
+    /// excerpts around the query terms, only filled by attach_snippets()
+    pub snippets: Vec<Snippet>,
+}
+
+impl SearchHit {
+    /// `metadata` comes from the index, archive entries have none on disk
+    pub fn new(path: PathBuf, score: f32, metadata: Option<&FileMetadata>) -> Self {
+        Self {
+            path,
+            score,
+            size: metadata.map(|meta| meta.size),
+            modified: metadata.map(|meta| meta.modified),
+            snippets: Vec::new(),
+        }
+    }
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.to_string_lossy().to_string())
+    }
+    pub fn folder(&self) -> String {
+        self.path
+            .parent()
+            .map(|parent| parent.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// the best hits of a search and the number of documents it matched
+#[derive(Clone, Debug, Default)]
+pub struct Ranking {
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+/// parses and runs `query` against `index`, drops the results its filters reject and keeps
+/// at most `limit` hits, best score first. A query made of filters only lists every matching
+/// document. Shared by the gtk front end and the command line so both rank the same way.
+pub fn rank(
+    index: &Index,
+    metadata: &IndexMetadata,
+    query: &str,
+    limit: usize,
+    reader: &DocumentReader,
+) -> Result<Ranking, ParseError> {
+    let parsed = query::parse(query)?;
+    let scores = match &parsed.query {
+        Some(parsed_query) => parsed_query.evaluate(index, reader),
+        None if !parsed.filters.is_empty() => all_documents(index),
+        None => return Ok(Ranking::default()),
+    };
+    let mut ranked: Vec<(PathBuf, f32, Option<FileMetadata>)> = scores
+        .into_iter()
+        .map(|(path, score)| {
+            let file_metadata = metadata.get(&path);
+            (path, score, file_metadata)
+        })
+        .filter(|(path, _, file_metadata)| {
+            parsed
+                .filters
+                .iter()
+                .all(|filter| filter.matches(path, file_metadata.as_ref()))
+        })
+        .collect();
+    ranked
+        .sort_by(|(path_a, a, _), (path_b, b, _)| b.total_cmp(a).then_with(|| path_a.cmp(path_b)));
+    let total = ranked.len();
+    let hits = ranked
+        .into_iter()
+        .take(limit)
+        .map(|(path, score, file_metadata)| SearchHit::new(path, score, file_metadata.as_ref()))
+        .collect();
+    Ok(Ranking { total, hits })
+}
+
+/// reads the `max_hits` first documents and extracts the snippets around the query terms,
+/// hits that already have snippets are left as they are. Kept apart from rank() since it
+/// reads every document again.
+pub fn attach_snippets(
+    hits: &mut [SearchHit],
+    query: &str,
+    max_hits: usize,
+    reader: &DocumentReader,
+) {
+    let terms = highlight_terms(query);
+    reader.expect(
+        hits.iter()
+            .take(max_hits)
+            .filter(|hit| hit.snippets.is_empty())
+            .map(|hit| hit.path.as_path()),
+    );
+    for hit in hits.iter_mut().take(max_hits) {
+        if !hit.snippets.is_empty() {
+            continue;
+        }
+        match reader.load_text(&hit.path) {
+            Ok(text) => hit.snippets = extract_snippets(&text, &terms, SNIPPETS_PER_HIT),
+            Err(e) => debug!("no snippet for {:?}: {}", hit.path, e),
+        }
+    }
+}
+
+/// What a search thread sends back to the main loop
+pub enum SearchProgress {
+    /// the ranked hits, shown as soon as they are known
+    Ranked(Ranking),
+    /// the best hits once their snippets are extracted
+    Snippets(Vec<SearchHit>),
+    Failed(ParseError),
+}
+
+/// ranks `query` then extracts the snippets of the `snippet_hits` best hits, the ones shown
+/// first, calling `report` after each step. `is_stale` is checked between the steps so that
+/// a query replaced by a newer one stops early. Like run_index_job this is free of any gtk
+/// type so it can run on a worker thread.
+pub fn run_search<S, F>(
+    index: &Index,
+    metadata: &IndexMetadata,
+    query: &str,
+    limit: usize,
+    snippet_hits: usize,
+    is_stale: S,
+    report: F,
+) where
+    S: Fn() -> bool,
+    F: Fn(SearchProgress),
+{
+    let reader = DocumentReader::default();
+    let ranking = match rank(index, metadata, query, limit, &reader) {
+        Ok(ranking) => ranking,
+        Err(e) => {
+            report(SearchProgress::Failed(e));
+            return;
+        }
+    };
+    if is_stale() {
+        return;
+    }
+    let mut hits: Vec<SearchHit> = ranking.hits.iter().take(snippet_hits).cloned().collect();
+    report(SearchProgress::Ranked(ranking));
+    attach_snippets(&mut hits, query, snippet_hits, &reader);
+    if is_stale() {
+        return;
+    }
+    report(SearchProgress::Snippets(hits));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path};
+
+    use search_engine::utils::generate_uid;
+
+    use super::*;
+
+    /// writes the documents in a temp dir and indexes them the way an index job does
+    fn index(dir: &Path, documents: &[(&str, &str)]) -> Index {
+        fs::create_dir_all(dir).unwrap();
+        let files: Vec<String> = documents
+            .iter()
+            .map(|(name, text)| {
+                let path = dir.join(name);
+                fs::write(&path, text).unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect();
+        let mut index = Index::new(files);
+        index.index_all();
+        index
+    }
+
+    fn test_dir() -> PathBuf {
+        env::temp_dir().join(format!("rustindexer-test-{}", generate_uid()))
+    }
+
+    fn names(ranking: &Ranking) -> Vec<String> {
+        ranking.hits.iter().map(SearchHit::file_name).collect()
+    }
+
+    fn sorted_names(ranking: &Ranking) -> Vec<String> {
+        let mut names = names(ranking);
+        names.sort();
+        names
+    }
+
+    fn search(index: &Index, query: &str, limit: usize) -> Ranking {
+        let metadata = IndexMetadata::default();
+        rank(index, &metadata, query, limit, &DocumentReader::default()).unwrap()
+    }
+
+    #[test]
+    fn boolean_queries_select_the_matching_documents() {
+        let dir = test_dir();
+        let index = index(
+            &dir,
+            &[("a.md", "rust gtk"), ("b.md", "rust"), ("c.md", "gtk java")],
+        );
+        for (query, expected) in [
+            ("rust gtk", &["a.md"][..]),
+            ("rust AND gtk", &["a.md"]),
+            ("rust OR java", &["a.md", "b.md", "c.md"]),
+            ("gtk -java", &["a.md"]),
+            ("NOT rust", &["c.md"]),
+            ("(rust OR java) NOT gtk", &["b.md"]),
+            ("Rust", &["a.md", "b.md"]),
+            ("python", &[]),
+        ] {
+            let ranking = search(&index, query, 10);
+            assert_eq!(sorted_names(&ranking), expected, "{}", query);
+            assert_eq!(ranking.total, expected.len(), "{}", query);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn filters_drop_the_results_they_reject() {
+        let dir = test_dir();
+        let long_text = format!("rust {}", "x".repeat(5000));
+        let index = index(
+            &dir,
+            &[
+                ("a.md", "rust"),
+                ("b.txt", &long_text),
+                ("notes-c.md", "gtk"),
+            ],
+        );
+        for (query, expected) in [
+            ("rust ext:md", &["a.md"][..]),
+            ("rust -ext:md", &["b.txt"]),
+            ("rust size:>1k", &["b.txt"]),
+            ("ext:md", &["a.md", "notes-c.md"]),
+            ("path:notes", &["notes-c.md"]),
+            ("gtk size:>1k", &[]),
+        ] {
+            let ranking = search(&index, query, 10);
+            assert_eq!(sorted_names(&ranking), expected, "{}", query);
+        }
+        // the metadata of the index is used before the file on disk
+        let mut metadata = IndexMetadata::default();
+        let file_metadata = FileMetadata {
+            size: 1 << 20,
+            modified: 0,
+            hash: None,
+        };
+        metadata.insert(&dir.join("notes-c.md"), file_metadata);
+        let reader = DocumentReader::default();
+        let ranking = rank(&index, &metadata, "gtk size:>1k", 10, &reader).unwrap();
+        assert_eq!(names(&ranking), ["notes-c.md"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hits_are_sorted_by_score_then_by_path() {
+        let dir = test_dir();
+        let index = index(
+            &dir,
+            &[
+                ("c.md", "common"),
+                ("a.md", "common"),
+                ("d.md", "common rare"),
+                ("b.md", "common"),
+            ],
+        );
+        let ranking = search(&index, "common OR rare", 10);
+        assert_eq!(names(&ranking), ["d.md", "a.md", "b.md", "c.md"]);
+        assert!(ranking.hits[0].score > ranking.hits[1].score);
+        // a term found in every document scores 0 everywhere, only the paths order them
+        let ranking = search(&index, "common", 10);
+        assert_eq!(names(&ranking), ["a.md", "b.md", "c.md", "d.md"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn limit_keeps_the_best_hits_and_total_counts_them_all() {
+        let dir = test_dir();
+        let index = index(
+            &dir,
+            &[
+                ("a.md", "common"),
+                ("b.md", "common rare"),
+                ("c.md", "common"),
+            ],
+        );
+        let ranking = search(&index, "common OR rare", 2);
+        assert_eq!(ranking.total, 3);
+        assert_eq!(names(&ranking), ["b.md", "a.md"]);
+
+        let ranking = search(&index, "common", 0);
+        assert_eq!(ranking.total, 3);
+        assert!(ranking.hits.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn empty_and_invalid_queries() {
+        let dir = test_dir();
+        let index = index(&dir, &[("a.md", "rust")]);
+        let ranking = search(&index, "  ", 10);
+        assert_eq!(ranking.total, 0);
+        assert!(ranking.hits.is_empty());
+        let metadata = IndexMetadata::default();
+        let reader = DocumentReader::default();
+        assert!(rank(&index, &metadata, "(rust", 10, &reader).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
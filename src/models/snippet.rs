@@ -0,0 +1,164 @@
+use std::ops::Range;
+
+use serde::Serialize;
+
+/// number of words kept on each side of a matched term
+const CONTEXT_WORDS: usize = 8;
+
+/// a short excerpt of a document, `highlights` are byte ranges of `text` matching a query term
+#[derive(Clone, Debug, Serialize)]
+pub struct Snippet {
+    pub text: String,
+    #[serde(skip)]
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// the words of a query, lowercased, as they are looked up in documents
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = words(query)
+        .into_iter()
+        .map(|range| query[range].to_lowercase())
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// byte ranges of the alphanumeric runs of `text`
+pub fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push(s..text.len());
+    }
+    words
+}
+
+/// byte ranges of every word of `text` equal to one of `terms` (case insensitive)
+pub fn find_terms(text: &str, terms: &[String]) -> Vec<Range<usize>> {
+    words(text)
+        .into_iter()
+        .filter(|range| terms.contains(&text[range.clone()].to_lowercase()))
+        .collect()
+}
+
+/// up to `max_snippets` excerpts of `text` around the occurrences of `terms`. Occurrences close
+/// to each other end up in the same snippet.
+pub fn extract_snippets(text: &str, terms: &[String], max_snippets: usize) -> Vec<Snippet> {
+    let words = words(text);
+    let mut snippets = Vec::new();
+    let mut covered_until = 0;
+    for (i, word) in words.iter().enumerate() {
+        if snippets.len() >= max_snippets {
+            break;
+        }
+        if i < covered_until || !terms.contains(&text[word.clone()].to_lowercase()) {
+            continue;
+        }
+        let first = i.saturating_sub(CONTEXT_WORDS);
+        let last = (i + CONTEXT_WORDS).min(words.len() - 1);
+        let (start, end) = (words[first].start, words[last].end);
+        let highlights = words[first..=last]
+            .iter()
+            .filter(|range| terms.contains(&text[(*range).clone()].to_lowercase()))
+            .map(|range| range.start - start..range.end - start)
+            .collect();
+        // line breaks and tabs are one byte like a space so the highlights stay valid
+        let excerpt: String = text[start..end]
+            .chars()
+            .map(|c| {
+                if c == '\n' || c == '\r' || c == '\t' {
+                    ' '
+                } else {
+                    c
+                }
+            })
+            .collect();
+        snippets.push(Snippet {
+            text: excerpt,
+            highlights,
+        });
+        covered_until = last + 1;
+    }
+    snippets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    fn highlighted(snippet: &Snippet) -> Vec<&str> {
+        snippet
+            .highlights
+            .iter()
+            .map(|range| &snippet.text[range.clone()])
+            .collect()
+    }
+
+    #[test]
+    fn snippets_keep_a_window_of_words_around_the_term() {
+        let words: Vec<String> = (0..30).map(|i| format!("w{}", i)).collect();
+        let text = words.join(" ");
+        let snippets = extract_snippets(&text, &terms(&["w15"]), 3);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(
+            snippets[0].text,
+            words[15 - CONTEXT_WORDS..=15 + CONTEXT_WORDS].join(" ")
+        );
+        assert_eq!(highlighted(&snippets[0]), ["w15"]);
+
+        // the window is cut at the start and the end of the text
+        let snippets = extract_snippets(&text, &terms(&["w0", "w29"]), 3);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].text, words[..=CONTEXT_WORDS].join(" "));
+        assert_eq!(snippets[1].text, words[29 - CONTEXT_WORDS..].join(" "));
+    }
+
+    #[test]
+    fn close_occurrences_share_a_snippet() {
+        let text = "one rust two gtk three four five six seven eight nine ten eleven twelve \
+                    thirteen fourteen fifteen sixteen seventeen eighteen rust";
+        let snippets = extract_snippets(text, &terms(&["rust", "gtk"]), 3);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(highlighted(&snippets[0]), ["rust", "gtk"]);
+        assert_eq!(highlighted(&snippets[1]), ["rust"]);
+        let snippets = extract_snippets(text, &terms(&["rust", "gtk"]), 1);
+        assert_eq!(snippets.len(), 1);
+    }
+
+    #[test]
+    fn highlights_are_byte_offsets_in_multibyte_text() {
+        let text = "L'été à Noël: crème brûlée\nÉTÉ\tdéjà vu";
+        let snippets = extract_snippets(text, &terms(&["été", "brûlée"]), 3);
+        assert_eq!(snippets.len(), 1);
+        let snippet = &snippets[0];
+        assert_eq!(snippet.text, "L'été à Noël: crème brûlée ÉTÉ déjà vu");
+        assert_eq!(highlighted(snippet), ["été", "brûlée", "ÉTÉ"]);
+        assert_eq!(snippet.highlights[0], 2..7);
+    }
+
+    #[test]
+    fn no_snippet_without_a_term() {
+        assert!(extract_snippets("nothing to see", &terms(&["rust"]), 3).is_empty());
+        assert!(extract_snippets("", &terms(&["rust"]), 3).is_empty());
+    }
+
+    #[test]
+    fn query_terms_are_lowercased_and_unique() {
+        assert_eq!(query_terms("Rust, rust-GTK été"), ["gtk", "rust", "été"]);
+    }
+}
@@ -2,8 +2,14 @@ use std::{cell::RefCell, ops::Index, rc::Rc, slice::SliceIndex};
 
 use gtk::{gio::FileInfo, prelude::*, ApplicationWindow, Button, Window};
 
+use crate::config::Settings;
+use crate::models::index_model::StoredIndexModel;
 use crate::views::{browse_view::BrowseView, main_view::MainView, search_view::SearchView};
 pub type StandardResult = Result<(), Box<dyn std::error::Error>>;
+/// the index searches run against, shared between the views and the controllers
+pub type ActiveIndex = Rc<RefCell<Option<StoredIndexModel>>>;
+/// the settings loaded at startup, replaced when the settings dialog saves them
+pub type SharedSettings = Rc<RefCell<Settings>>;
 pub struct VecInfo {
     pub vec_info: Vec<FileInfo>,
 }
@@ -0,0 +1,103 @@
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use gtk::glib;
+use serde::Serialize;
+
+use crate::types::StandardResult;
+
+/// formats a number of bytes for display, e.g. 1536 => "1.5 KB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// formats a duration as "1h 02m", "3m 20s" or "12s"
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// serializes `value` to a temporary file next to `path` and then renames it, so that a crash
+/// while writing never leaves a truncated file at the location we load from
+pub fn write_json_atomic<T: Serialize>(value: &T, path: &Path) -> StandardResult {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    let mut writer = BufWriter::new(fs::File::create(&tmp_path)?);
+    serde_json::to_writer(&mut writer, value)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// formats a unix timestamp in local time, e.g. "2024-03-01 14:05"
+pub fn format_timestamp(secs: u64) -> String {
+    glib::DateTime::from_unix_local(secs as i64)
+        .and_then(|date| date.format("%Y-%m-%d %H:%M"))
+        .map(|formatted| formatted.to_string())
+        .unwrap_or_default()
+}
+
+/// groups the digits by three for display, e.g. 12304 => "12 304"
+pub fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(' ');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_count_groups_digits_by_three() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1 000");
+        assert_eq!(format_count(12304), "12 304");
+        assert_eq!(format_count(1234567), "1 234 567");
+    }
+
+    #[test]
+    fn format_bytes_uses_the_largest_unit() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 << 30), "3.0 GB");
+    }
+
+    #[test]
+    fn format_duration_shows_two_units() {
+        assert_eq!(format_duration(Duration::from_secs(12)), "12s");
+        assert_eq!(format_duration(Duration::from_secs(200)), "3m 20s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h 02m");
+    }
+}
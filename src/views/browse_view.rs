@@ -2,7 +2,7 @@ use crate::config::MIN_SCROLL_WINDOW_HEIGHT;
 use crate::log::{debug, info};
 use crate::models::index_model::StoredIndexModel;
 use crate::types::{Controller, StandardResult};
-use crate::widgets::screen::ScreenOutput;
+use crate::widgets::preview::PreviewPane;
 use core::cell::Cell;
 use gtk::gio::{Cancellable, File, FileEnumerator, FileInfo, FileQueryInfoFlags, FileType};
 use gtk::glib::ToValue;
@@ -61,7 +61,8 @@ pub struct BrowseView {
     pub close_button: Button,
     pub search_bar: SearchBar,
     pub search_entry: SearchEntry,
-    pub output_screen: ScreenOutput,
+    pub preview: PreviewPane,
+    browser_box: gtk::Box,
     pub info_list: Rc<RefCell<Vec<FileInfo>>>,
     pub selection_index: Rc<RefCell<u32>>,
     pub hash_info_index: Rc<RefCell<HashMap<usize, FileInfo>>>,
@@ -73,8 +74,8 @@ impl BrowseView {
     ///up or DirectoryList and to pass data to the MainController, especially
     ///the dynamic_path variable which allows a browsing of the tree direcctory
     ///and retains the path to the directory we want to index with the
-    ///index_button of the MainView
-    pub fn new() -> Self {
+    ///index_button of the MainView. The browsing starts in `index_root`
+    pub fn new(index_root: &Path) -> Self {
         let window = Window::new();
         let label_selected_folder = Label::new(Some("select a folder"));
         let hash_info_index = Rc::new(RefCell::new(HashMap::new()));
@@ -101,13 +102,10 @@ impl BrowseView {
         let browse_back_button = Button::new();
         let search_bar = SearchBar::new();
         let search_entry = SearchEntry::new();
-        let output_screen = ScreenOutput::new();
+        let preview = PreviewPane::new();
+        let browser_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
         let info_list = Rc::new(RefCell::new(Vec::new()));
-        let static_path = PathBuf::from(
-            std::env::var("HOME")
-                .unwrap_or_else(|_| "/home".to_string())
-                .as_str(),
-        );
+        let static_path = index_root.to_path_buf();
         let dynamic_path = Rc::new(RefCell::new(static_path.to_string_lossy().to_string()));
         let default_dir = File::for_path(static_path.clone());
         let directories = DirectoryList::new(Some("standard::name"), Some(&default_dir));
@@ -131,7 +129,8 @@ impl BrowseView {
             close_button,
             search_bar,
             search_entry,
-            output_screen,
+            preview,
+            browser_box,
             info_list,
             selection_index,
             hash_info_index,
@@ -147,7 +146,10 @@ impl BrowseView {
         self.gtk_box.append(&self.search_entry);
         self.gtk_box.append(&self.search_bar);
         self.gtk_box.append(&self.label_selected_folder);
-        self.gtk_box.append(&self.scroll_window);
+        // the preview shows the file selected in the list next to it
+        self.browser_box.append(&self.scroll_window);
+        self.browser_box.append(&self.preview.gtk_box);
+        self.gtk_box.append(&self.browser_box);
         self.gtk_box.append(&self.browse_back_button);
         self.gtk_box.append(&self.close_button);
         self.window.set_child(Some(&self.gtk_box));
@@ -474,6 +476,7 @@ impl BrowseView {
         selection.connect_selection_changed(move |selection, _, _| {
             let index: usize = selection.selected() as usize; // the selected() method returns a u32
             self_cloned.set_label_on_selected_item(&index); // this is to set the main label
+            self_cloned.preview_selected_item(&index);
             self_cloned.selection_callback();
         });
     }
@@ -483,7 +486,15 @@ impl BrowseView {
         factory.connect_bind(move |_, list_item| {});
     }
 
-    pub fn update_screen(&self, data: &str) {
-        self.output_screen.update_buffer(data)
+    /// previews the selected item when it is a regular file
+    fn preview_selected_item(&self, index: &usize) {
+        if let Some(info) = self.info_list.borrow().get(*index) {
+            let path = PathBuf::from(self.dynamic_path.borrow().as_str()).join(info.name());
+            if path.is_file() {
+                self.preview.show_document(&path, &[]);
+            } else {
+                self.preview.show_message("");
+            }
+        }
     }
 }
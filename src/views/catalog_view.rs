@@ -0,0 +1,145 @@
+use std::rc::Rc;
+
+use gtk::{prelude::*, Align, Button, Entry, Label, ListBox, Orientation, ScrolledWindow, Window};
+
+use crate::config::MIN_SCROLL_WINDOW_HEIGHT;
+use crate::models::catalog::{CatalogEntry, IndexCatalog};
+use crate::utils::{format_bytes, format_timestamp};
+
+/// what the user asked for on one row of the catalog dialog
+#[derive(Clone, Debug)]
+pub enum CatalogAction {
+    Open(String),
+    Rename(String, String),
+    Rebuild(String),
+    Delete(String),
+}
+
+/// Dialog listing every index of the catalog. The view only displays the rows, the actions
+/// are forwarded to the callback given to refresh() so the controller decides what they do.
+#[derive(Clone)]
+pub struct CatalogView {
+    pub window: Window,
+    pub list_box: ListBox,
+    pub scroll_window: ScrolledWindow,
+    pub gtk_box: gtk::Box,
+    pub empty_label: Label,
+    pub close_button: Button,
+}
+
+impl CatalogView {
+    pub fn new() -> Self {
+        let window = Window::builder().title("Indexes").build();
+        let list_box = ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .show_separators(true)
+            .build();
+        let scroll_window = ScrolledWindow::builder()
+            .min_content_height(MIN_SCROLL_WINDOW_HEIGHT)
+            .min_content_width(600)
+            .build();
+        let gtk_box = gtk::Box::builder()
+            .orientation(Orientation::Vertical)
+            .margin_top(12)
+            .margin_bottom(12)
+            .margin_start(12)
+            .margin_end(12)
+            .spacing(12)
+            .build();
+        let empty_label = Label::new(Some("no index yet, browse a folder and index it"));
+        let close_button = Button::with_label("Close");
+        Self {
+            window,
+            list_box,
+            scroll_window,
+            gtk_box,
+            empty_label,
+            close_button,
+        }
+    }
+
+    pub fn build_ui(&self) {
+        self.scroll_window.set_child(Some(&self.list_box));
+        self.gtk_box.append(&self.empty_label);
+        self.gtk_box.append(&self.scroll_window);
+        self.gtk_box.append(&self.close_button);
+        self.window.set_child(Some(&self.gtk_box));
+        let window = self.window.clone();
+        self.close_button.connect_clicked(move |_| window.close());
+    }
+
+    /// rebuilds one row per catalog entry, `active_id` is the index searches currently use
+    pub fn refresh(
+        &self,
+        catalog: &IndexCatalog,
+        active_id: Option<&str>,
+        on_action: Rc<dyn Fn(CatalogAction)>,
+    ) {
+        self.list_box.remove_all();
+        self.empty_label.set_visible(catalog.entries.is_empty());
+        let mut entries = catalog.entries.clone();
+        entries.sort_by(|a, b| b.updated.cmp(&a.updated));
+        for entry in entries {
+            let is_active = active_id == Some(entry.id.as_str());
+            self.list_box
+                .append(&self.build_row(&entry, is_active, on_action.clone()));
+        }
+    }
+
+    fn build_row(
+        &self,
+        entry: &CatalogEntry,
+        is_active: bool,
+        on_action: Rc<dyn Fn(CatalogAction)>,
+    ) -> gtk::Box {
+        let row = gtk::Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .margin_top(6)
+            .margin_bottom(6)
+            .build();
+        let name_entry = Entry::builder().text(entry.name.as_str()).build();
+        let details = Label::builder()
+            .label(
+                format!(
+                    "{}{}\n{} documents - {} on disk\ncreated {} - updated {}",
+                    if is_active { "(active) " } else { "" },
+                    entry.root.to_string_lossy(),
+                    entry.document_count,
+                    format_bytes(entry.size_on_disk),
+                    format_timestamp(entry.created),
+                    format_timestamp(entry.updated)
+                )
+                .as_str(),
+            )
+            .halign(Align::Start)
+            .build();
+        let buttons = gtk::Box::new(Orientation::Horizontal, 6);
+        let open = Button::with_label("Open");
+        let rename = Button::with_label("Rename");
+        let rebuild = Button::with_label("Rebuild");
+        let delete = Button::with_label("Delete");
+        open.add_css_class("suggested-action");
+        delete.add_css_class("destructive-action");
+
+        let (id, callback) = (entry.id.clone(), on_action.clone());
+        open.connect_clicked(move |_| callback(CatalogAction::Open(id.clone())));
+        let (id, callback, name) = (entry.id.clone(), on_action.clone(), name_entry.clone());
+        rename.connect_clicked(move |_| {
+            callback(CatalogAction::Rename(id.clone(), name.text().to_string()))
+        });
+        let (id, callback) = (entry.id.clone(), on_action.clone());
+        rebuild.connect_clicked(move |_| callback(CatalogAction::Rebuild(id.clone())));
+        let (id, callback) = (entry.id.clone(), on_action);
+        delete.connect_clicked(move |_| callback(CatalogAction::Delete(id.clone())));
+
+        buttons.append(&open);
+        buttons.append(&rename);
+        buttons.append(&rebuild);
+        buttons.append(&delete);
+        row.append(&name_entry);
+        row.append(&details);
+        row.append(&buttons);
+        row
+    }
+}
@@ -0,0 +1,129 @@
+use gtk::{
+    prelude::*, Align, Button, CheckButton, Entry, Grid, Label, Orientation, SpinButton, Window,
+};
+
+use crate::models::index_rules::{parse_patterns, IndexRules};
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// Dialog shown before an indexing job starts, to review what gets indexed under the folder.
+/// Starting the job is done by the controller connected to start_button.
+#[derive(Clone)]
+pub struct IndexRulesView {
+    pub window: Window,
+    pub grid: Grid,
+    pub folder_label: Label,
+    pub include: Entry,
+    pub exclude: Entry,
+    pub respect_ignore_files: CheckButton,
+    pub include_hidden: CheckButton,
+    pub max_file_size: SpinButton,
+    pub max_depth: SpinButton,
+    pub descend_archives: CheckButton,
+    pub start_button: Button,
+    pub cancel_button: Button,
+}
+
+impl IndexRulesView {
+    pub fn new() -> Self {
+        let window = Window::builder()
+            .title("Index a folder")
+            .modal(true)
+            .build();
+        let grid = Grid::builder()
+            .margin_top(12)
+            .margin_bottom(12)
+            .margin_start(12)
+            .margin_end(12)
+            .row_spacing(12)
+            .column_spacing(12)
+            .build();
+        let folder_label = Label::builder().halign(Align::Start).build();
+        let include = Entry::builder()
+            .placeholder_text("every file, or comma separated globs, e.g. **/*.md")
+            .build();
+        let exclude = Entry::builder()
+            .placeholder_text("comma separated globs, e.g. **/target/**")
+            .build();
+        let respect_ignore_files = CheckButton::with_label("skip what .gitignore and .ignore list");
+        let include_hidden = CheckButton::with_label("index hidden files and folders");
+        // sizes are edited in MB, 0 disables the limit
+        let max_file_size = SpinButton::with_range(0.0, 4096.0, 1.0);
+        let max_depth = SpinButton::with_range(0.0, 100.0, 1.0);
+        max_depth.set_tooltip_text(Some("1 indexes the folder itself only, 0 has no limit"));
+        let descend_archives =
+            CheckButton::with_label("index the files inside zip and tar archives");
+        let start_button = Button::with_label("Index");
+        let cancel_button = Button::with_label("Cancel");
+        Self {
+            window,
+            grid,
+            folder_label,
+            include,
+            exclude,
+            respect_ignore_files,
+            include_hidden,
+            max_file_size,
+            max_depth,
+            descend_archives,
+            start_button,
+            cancel_button,
+        }
+    }
+
+    pub fn build_ui(&self) {
+        let rows: [(&str, &gtk::Widget); 8] = [
+            ("Folder", self.folder_label.upcast_ref()),
+            ("Include patterns", self.include.upcast_ref()),
+            ("Exclude patterns", self.exclude.upcast_ref()),
+            ("", self.respect_ignore_files.upcast_ref()),
+            ("", self.include_hidden.upcast_ref()),
+            ("Max file size (MB)", self.max_file_size.upcast_ref()),
+            ("Max depth", self.max_depth.upcast_ref()),
+            ("", self.descend_archives.upcast_ref()),
+        ];
+        for (row, (label, widget)) in rows.iter().enumerate() {
+            let label = Label::builder().label(*label).halign(Align::End).build();
+            self.grid.attach(&label, 0, row as i32, 1, 1);
+            self.grid.attach(*widget, 1, row as i32, 1, 1);
+        }
+        let buttons = gtk::Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(12)
+            .halign(Align::End)
+            .build();
+        buttons.append(&self.cancel_button);
+        buttons.append(&self.start_button);
+        self.grid.attach(&buttons, 0, rows.len() as i32, 2, 1);
+        self.window.set_child(Some(&self.grid));
+        self.start_button.add_css_class("suggested-action");
+
+        let window = self.window.clone();
+        self.cancel_button.connect_clicked(move |_| window.close());
+    }
+
+    pub fn fill(&self, root: &str, rules: &IndexRules) {
+        self.folder_label.set_text(root);
+        self.include.set_text(rules.include.join(", ").as_str());
+        self.exclude.set_text(rules.exclude.join(", ").as_str());
+        self.respect_ignore_files
+            .set_active(rules.respect_ignore_files);
+        self.include_hidden.set_active(rules.include_hidden);
+        self.max_file_size
+            .set_value((rules.max_file_size / MEGABYTE) as f64);
+        self.max_depth.set_value(rules.max_depth as f64);
+        self.descend_archives.set_active(rules.descend_archives);
+    }
+
+    pub fn read(&self) -> IndexRules {
+        IndexRules {
+            include: parse_patterns(self.include.text().as_str()),
+            exclude: parse_patterns(self.exclude.text().as_str()),
+            respect_ignore_files: self.respect_ignore_files.is_active(),
+            include_hidden: self.include_hidden.is_active(),
+            max_file_size: self.max_file_size.value_as_int() as u64 * MEGABYTE,
+            max_depth: self.max_depth.value_as_int() as usize,
+            descend_archives: self.descend_archives.is_active(),
+        }
+    }
+}
@@ -3,14 +3,13 @@ use std::rc::Rc;
 
 use gtk::gio::{File, FileInfo};
 use gtk::{prelude::*, Align, Label, SearchEntry};
-use gtk::{Application, ApplicationWindow, Button, Orientation};
+use gtk::{AlertDialog, Application, ApplicationWindow, Button, Orientation, Switch, Window};
 
-use crate::config::{APP_WINDOW_HEIGHT, APP_WINDOW_WIDTH};
 use crate::controllers::main_controller::MainController;
-use crate::controllers::search_controller::SearchController;
 use crate::models::index_model::StoredIndexModel;
-use crate::types::Controller;
+use crate::types::{ActiveIndex, Controller, SharedSettings};
 use crate::widgets::menu_bar::CustomBar;
+use crate::widgets::progress::IndexProgressBar;
 
 use super::browse_view::BrowseView;
 use super::search_view::SearchView;
@@ -24,31 +23,49 @@ use super::search_view::SearchView;
 #[derive(Clone)]
 pub struct MainView {
     pub input_view: SearchView,
-    pub model: Option<StoredIndexModel>,
+    pub model: ActiveIndex,
+    pub settings: SharedSettings,
+    startup_error: Rc<RefCell<Option<String>>>,
     pub directory: Rc<RefCell<Option<File>>>,
     // browse_view: BrowseView,
-    headerbar: CustomBar,
+    pub headerbar: CustomBar,
     main_box: gtk::Box,
     header_box: gtk::Box,
     label_box: gtk::Box,
     index_box: gtk::Box,
     gtk_box: gtk::Box,
     pub folder_label: Label,
+    pub active_index_label: Label,
+    empty_state: Label,
     legend: Label,
     pub browse: Button,
     pub index_button: Button,
+    pub pause_button: Button,
+    pub cancel_button: Button,
+    pub progress: IndexProgressBar,
+    /// "index up to date" or "3 pending changes", next to the switch turning the watcher on
+    pub watch_label: Label,
+    pub watch_switch: Switch,
+    watch_box: gtk::Box,
+    /// called every time the active index is replaced or cleared
+    active_index_listeners: Rc<RefCell<Vec<Rc<dyn Fn()>>>>,
     pub exit_button: Button,
 }
 impl Controller for MainView {}
 
 impl MainView {
-    pub fn new() -> Self {
-        // let model = StoredIndexModel::new();
-        let model = Some(StoredIndexModel::new());
+    pub fn new(settings: &SharedSettings) -> Self {
+        // a missing index is a normal state, an unreadable one is reported once the window exists
+        let (model, startup_error) = match StoredIndexModel::load_last() {
+            Ok(model) => (model, None),
+            Err(e) => (None, Some(e)),
+        };
+        let model = Rc::new(RefCell::new(model));
+        let startup_error = Rc::new(RefCell::new(startup_error));
         let directory = Rc::new(RefCell::new(None));
         // let browse_view = BrowseView::new(&model);
 
-        let input_view = SearchView::new();
+        let input_view = SearchView::new(settings);
         let main_box = gtk::Box::builder()
             .orientation(Orientation::Vertical)
             .halign(Align::Center)
@@ -88,8 +105,25 @@ impl MainView {
         let headerbar = CustomBar::new();
         let legend = Label::new(Some("folder to index: "));
         let folder_label = Label::new(Some("<select a folder>"));
+        let active_index_label = Label::new(None);
+        let empty_state = Label::new(Some("no index yet - pick a folder to index"));
         let browse = Button::builder().label("browse").build();
         let index_button = Button::builder().label("index folder").build();
+        let pause_button = Button::builder().label("pause").visible(false).build();
+        let cancel_button = Button::builder().label("cancel").visible(false).build();
+        let progress = IndexProgressBar::new();
+        let watch_label = Label::new(None);
+        let watch_switch = Switch::builder()
+            .active(settings.borrow().watch_changes)
+            .tooltip_text("keep the active index up to date with its folder")
+            .valign(Align::Center)
+            .build();
+        let watch_box = gtk::Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(6)
+            .halign(Align::Center)
+            .build();
+        let active_index_listeners = Rc::new(RefCell::new(Vec::new()));
 
         let exit_button = Button::builder()
             .label("Exit")
@@ -101,6 +135,8 @@ impl MainView {
         Self {
             // browse_view,
             model,
+            settings: settings.clone(),
+            startup_error,
             directory,
             input_view,
             headerbar,
@@ -111,23 +147,35 @@ impl MainView {
             label_box,
             gtk_box,
             folder_label,
+            active_index_label,
+            empty_state,
             browse,
             index_button,
+            pause_button,
+            cancel_button,
+            progress,
+            watch_label,
+            watch_switch,
+            watch_box,
+            active_index_listeners,
             exit_button,
         }
     }
     ///this creates the main window, and several buttons that allows some functionnalities
-    ///set_controllers() defines connect_clicked methods called on each button and triggers the controllers that handles the main
-    ///logic of the app
+    ///the controllers connected in main() give the buttons their behaviour
     pub fn build_ui(&mut self, app: &Application) {
+        let (width, height) = {
+            let settings = self.settings.borrow();
+            (settings.window_width, settings.window_height)
+        };
         let win = ApplicationWindow::builder()
             // .resizable(false) //this can prevent i3wm to resize the window
             .application(app)
             .child(&self.main_box)
             .title("TermiRust")
             .show_menubar(true)
-            // .default_width(APP_WINDOW_WIDTH)
-            // .default_height(APP_WINDOW_HEIGHT)
+            .default_width(width)
+            .default_height(height)
             .build();
         self.input_view.build_ui(&win);
         self.headerbar.build();
@@ -135,19 +183,31 @@ impl MainView {
         // self.header_box.append(&self.headerbar.gtk_box_menu);
         self.gtk_box.append(&self.browse);
         self.gtk_box.append(&self.index_button);
+        self.gtk_box.append(&self.pause_button);
+        self.gtk_box.append(&self.cancel_button);
         self.label_box.append(&self.legend);
         self.label_box.append(&self.folder_label);
         self.index_box.append(&self.label_box);
         self.index_box.append(&self.gtk_box);
+        self.index_box.append(&self.progress.gtk_box);
+        self.index_box.append(&self.active_index_label);
+        self.watch_box.append(&Label::new(Some("watch folder")));
+        self.watch_box.append(&self.watch_switch);
+        self.watch_box.append(&self.watch_label);
+        self.index_box.append(&self.watch_box);
         self.main_box.append(&self.header_box);
         self.main_box.append(&self.index_box);
+        self.main_box.append(&self.empty_state);
         self.main_box.append(&self.input_view.gtk_box);
         self.main_box.append(&self.exit_button);
         self.add_style();
-        // self.set_controllers();
         self.handle_exit(&self.exit_button, &win);
 
         win.present();
+        self.refresh_active_index();
+        if let Some(e) = self.startup_error.take() {
+            self.show_error("The stored index could not be loaded", &e);
+        }
 
         {
             let x = "string";
@@ -156,14 +216,59 @@ impl MainView {
     }
     fn add_style(&self) {
         self.exit_button.add_css_class("destructive-action");
-        self.index_button.add_css_class("suggested-action")
+        self.index_button.add_css_class("suggested-action");
+        self.cancel_button.add_css_class("destructive-action");
+        self.empty_state.add_css_class("title-3");
+    }
+    /// makes `model` the index that searches run against
+    pub fn set_active_index(&self, model: StoredIndexModel) {
+        self.model.replace(Some(model));
+        self.refresh_active_index();
+    }
+    pub fn clear_active_index(&self) {
+        self.model.replace(None);
+        self.refresh_active_index();
+    }
+    /// updates the active index label, and swaps the search widgets for the empty-state
+    /// message when there is nothing to search in
+    pub fn refresh_active_index(&self) {
+        let has_index = self.model.borrow().is_some();
+        let text = match self.model.borrow().as_ref() {
+            Some(model) => format!("active index: {}", model.name),
+            None => String::from("active index: none"),
+        };
+        self.active_index_label.set_text(text.as_str());
+        self.empty_state.set_visible(!has_index);
+        self.input_view.search_button.set_sensitive(has_index);
+        // cloned so a listener can replace the active index itself
+        let listeners = self.active_index_listeners.borrow().clone();
+        for listener in listeners {
+            listener();
+        }
+    }
+    pub fn connect_active_index_changed<F: Fn() + 'static>(&self, callback: F) {
+        self.active_index_listeners
+            .borrow_mut()
+            .push(Rc::new(callback));
+    }
+    /// shows a modal dialog over the main window with the underlying error
+    pub fn show_error(&self, message: &str, detail: &str) {
+        let window = self.main_box.root().and_downcast::<Window>();
+        let dialog = AlertDialog::builder()
+            .modal(true)
+            .message(message)
+            .detail(detail)
+            .build();
+        dialog.show(window.as_ref());
+    }
+    /// swaps the "index folder" button for the pause/cancel controls while a job runs
+    pub fn set_indexing(&self, running: bool) {
+        self.index_button.set_visible(!running);
+        self.pause_button.set_visible(running);
+        self.pause_button.set_label("pause");
+        self.cancel_button.set_visible(running);
+        self.cancel_button.set_sensitive(true);
     }
-    // fn set_controllers(&mut self) {
-    //     let search_controller = SearchController::new(&self.input_view);
-
-    //     search_controller.handle_activate();
-    //     search_controller.handle_click_search_button();
-    // }
     pub fn connect_index_clicked<F: Fn() + 'static>(&self, callback: F) {
         self.index_button.connect_clicked(move |_| callback());
     }
@@ -1,3 +1,6 @@
 pub mod browse_view;
+pub mod catalog_view;
+pub mod index_rules_view;
 pub mod main_view;
 pub mod search_view;
+pub mod settings_view;
@@ -1,19 +1,56 @@
-use crate::controllers::search_controller::SearchController;
 use crate::custom_button::CustomButton;
+use crate::models::filter::Filter;
 use crate::models::index_model::StoredIndexModel;
+use crate::models::pagination::ResultPages;
+use crate::models::query::{self, ParseError};
+use crate::models::search_model::{Ranking, SearchHit};
+use crate::types::SharedSettings;
+use crate::widgets::filter_bar::FilterBar;
+use crate::widgets::preview::PreviewPane;
+use crate::widgets::result_list::ResultList;
 use crate::widgets::screen::ScreenOutput;
+use crate::widgets::suggestions::SuggestionPopover;
 use gtk::{glib::SignalHandlerId, prelude::*, Align, ApplicationWindow};
-use gtk::{Button, SearchBar, SearchEntry, Window};
+use gtk::{Button, Label, Paned, SearchBar, SearchEntry, ToggleButton, Window};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+/// how long the entry waits after the last keystroke before searching
+const SEARCH_DELAY_MS: u32 = 300;
+
 #[derive(Clone)]
 pub struct SearchView {
     pub gtk_box: gtk::Box,
     pub search_button: Button,
     pub search_bar: SearchBar,
     pub search_entry: SearchEntry,
-    pub output_screen: ScreenOutput,
+    /// pins the query of the entry as a saved search
+    pub pin_button: ToggleButton,
+    /// past and saved queries completing the entry
+    pub suggestions: SuggestionPopover,
+    entry_box: gtk::Box,
+    /// syntax errors of the query, shown right under the entry
+    pub query_error_label: Label,
+    /// the filters of the query as chips, under the entry
+    pub filter_bar: FilterBar,
+    pub results: ResultList,
+    pub snippet_screen: ScreenOutput,
+    pub status_label: Label,
+    /// writes the results of the last search to a file, see SearchController::export()
+    pub export_button: Button,
+    pub preview: PreviewPane,
+    /// results on the left, preview of the selected result on the right
+    pub paned: Paned,
+    results_box: gtk::Box,
+    /// the query the displayed results come from, used to highlight the preview
+    pub last_query: Rc<RefCell<String>>,
+    /// every hit kept for the last query, the list only displays the range of `pages`
+    pub hits: Rc<RefCell<Vec<SearchHit>>>,
+    pub pages: Rc<RefCell<ResultPages>>,
+    settings: SharedSettings,
 }
 impl SearchView {
-    pub fn new() -> Self {
+    pub fn new(settings: &SharedSettings) -> Self {
         let gtk_box = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .margin_top(12)
@@ -26,14 +63,62 @@ impl SearchView {
         let search_button = Button::with_label("Search");
         let search_bar = SearchBar::new();
         let search_entry = SearchEntry::new();
-        let output_screen = ScreenOutput::new();
+        let pin_button = ToggleButton::builder()
+            .icon_name("starred-symbolic")
+            .tooltip_text("Save this search")
+            .build();
+        let suggestions = SuggestionPopover::new(&search_entry);
+        let entry_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let query_error_label = Label::builder().halign(Align::Start).visible(false).build();
+        query_error_label.add_css_class("error");
+        let filter_bar = FilterBar::new();
+        let results = ResultList::new();
+        let snippet_screen = ScreenOutput::new();
+        snippet_screen.label.set_text("Snippets");
+        snippet_screen.scroll_win.set_min_content_height(200);
+        snippet_screen.text_view.set_height_request(200);
+        snippet_screen
+            .text_view
+            .set_wrap_mode(gtk::WrapMode::WordChar);
+        let status_label = Label::builder()
+            .halign(Align::Start)
+            .selectable(true)
+            .build();
+        let export_button = Button::builder()
+            .label("Export results…")
+            .sensitive(false)
+            .build();
+        let preview = PreviewPane::new();
+        let results_box = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        let paned = Paned::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .wide_handle(true)
+            .build();
+        let last_query = Rc::new(RefCell::new(String::new()));
+        let hits = Rc::new(RefCell::new(Vec::new()));
+        let pages = Rc::new(RefCell::new(ResultPages::default()));
 
         Self {
             gtk_box,
             search_button,
             search_bar,
             search_entry,
-            output_screen,
+            pin_button,
+            suggestions,
+            entry_box,
+            query_error_label,
+            filter_bar,
+            results,
+            snippet_screen,
+            status_label,
+            export_button,
+            preview,
+            paned,
+            results_box,
+            last_query,
+            hits,
+            pages,
+            settings: settings.clone(),
         }
     }
 
@@ -42,16 +127,168 @@ impl SearchView {
         self.search_entry
             .set_placeholder_text(Some("chercher par mot-clé"));
         self.search_bar.set_key_capture_widget(Some(main_window));
-        self.gtk_box.append(&self.search_entry);
+        self.search_entry.set_tooltip_text(Some(
+            "words are combined with AND, use OR, NOT or -word, (parentheses) and \"exact phrases\"",
+        ));
+        self.search_entry.set_hexpand(true);
+        self.entry_box.append(&self.search_entry);
+        self.entry_box.append(&self.pin_button);
+        self.gtk_box.append(&self.entry_box);
+        self.gtk_box.append(&self.query_error_label);
+        self.gtk_box.append(&self.filter_bar.gtk_box);
         self.gtk_box.append(&self.search_bar);
         self.gtk_box.append(&self.search_button);
-        self.gtk_box.append(&self.output_screen.gtk_box);
+        self.results_box.append(&self.results.gtk_box);
+        let status_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        self.status_label.set_hexpand(true);
+        status_box.append(&self.status_label);
+        status_box.append(&self.export_button);
+        self.results_box.append(&status_box);
+        self.results_box.append(&self.snippet_screen.gtk_box);
+        self.paned.set_start_child(Some(&self.results_box));
+        self.paned.set_end_child(Some(&self.preview.gtk_box));
+        self.gtk_box.append(&self.paned);
 
+        self.handle_filter_bar();
         self.add_style();
     }
+    /// keeps the chips in sync with the entry, adding or removing a chip edits the query, which
+    /// runs the search again through search-changed
+    fn handle_filter_bar(&self) {
+        let view = self.clone();
+        self.search_entry
+            .connect_changed(move |_| view.refresh_filter_chips());
+        let view = self.clone();
+        self.filter_bar.connect_add(move |word| {
+            let text = view.search_entry.text();
+            let text = format!("{} {}", text.trim_end(), word);
+            view.search_entry.set_text(text.trim_start());
+        });
+    }
+    /// a query with a syntax error keeps the chips of the last valid one
+    fn refresh_filter_chips(&self) {
+        let Ok(parsed) = query::parse(self.search_entry.text().as_str()) else {
+            return;
+        };
+        let view = self.clone();
+        let on_remove = Rc::new(move |filter: &Filter| {
+            let text = remove_span(view.search_entry.text().as_str(), filter.span.clone());
+            view.search_entry.set_text(&text);
+        });
+        self.filter_bar.set_filters(&parsed.filters, on_remove);
+    }
     fn add_style(&self) {}
-    pub fn update_screen(&self, data: &str) {
-        self.output_screen.update_buffer(data)
+    /// displays the first page of `ranking`, the page size is read from the settings
+    pub fn show_results(&self, query: &str, ranking: Ranking) {
+        let (page_size, load_more) = {
+            let settings = self.settings.borrow();
+            (settings.page_size, settings.load_more)
+        };
+        self.last_query.replace(query.to_string());
+        self.clear_query_error();
+        self.status_label.set_text("");
+        self.preview.show_message("select a result to preview it");
+        self.pages.replace(ResultPages::new(
+            ranking.total,
+            ranking.hits.len(),
+            page_size,
+            load_more,
+        ));
+        self.export_button.set_sensitive(!ranking.hits.is_empty());
+        self.hits.replace(ranking.hits);
+        self.render_page();
+    }
+    fn render_page(&self) {
+        let pages = self.pages.borrow();
+        let hits = self.hits.borrow();
+        let top_score = hits.first().map(|hit| hit.score).unwrap_or(0.0);
+        let page = hits[pages.range()].to_vec();
+        self.show_snippets(&page);
+        self.results.set_results(page, top_score);
+        self.results.pager.update(&pages);
+    }
+    /// Returns the range of hits displayed for the first time, their snippets still have to
+    /// be extracted.
+    pub fn next_page(&self) -> Range<usize> {
+        let added = self.pages.borrow_mut().next();
+        self.render_page();
+        added
+    }
+    pub fn previous_page(&self) -> Range<usize> {
+        let range = self.pages.borrow_mut().previous();
+        self.render_page();
+        range
+    }
+    pub fn hits_in(&self, range: Range<usize>) -> Vec<SearchHit> {
+        let hits = self.hits.borrow();
+        let end = range.end.min(hits.len());
+        hits[range.start.min(end)..end].to_vec()
+    }
+    /// stores the snippets extracted for the hits starting at `start` and shows the snippets
+    /// of the displayed range
+    pub fn set_snippets(&self, start: usize, with_snippets: Vec<SearchHit>) {
+        {
+            let mut hits = self.hits.borrow_mut();
+            for (hit, extracted) in hits.iter_mut().skip(start).zip(with_snippets) {
+                hit.snippets = extracted.snippets;
+            }
+        }
+        let range = self.pages.borrow().range();
+        self.show_snippets(&self.hits_in(range));
+    }
+    /// keeps the previous results and points at the syntax error
+    pub fn show_query_error(&self, error: &ParseError) {
+        self.query_error_label.set_text(&error.to_string());
+        self.query_error_label.set_visible(true);
+        self.search_entry.add_css_class("error");
+    }
+    pub fn clear_query_error(&self) {
+        self.query_error_label.set_text("");
+        self.query_error_label.set_visible(false);
+        self.search_entry.remove_css_class("error");
+    }
+    pub fn clear_results(&self) {
+        self.hits.replace(Vec::new());
+        self.pages.replace(ResultPages::default());
+        self.export_button.set_sensitive(false);
+        self.status_label.set_text("");
+        self.snippet_screen.clear_buffer();
+        self.results.clear();
+    }
+    /// each hit with snippets gets its path followed by the snippets, terms highlighted
+    pub fn show_snippets(&self, hits: &[SearchHit]) {
+        self.snippet_screen.clear_buffer();
+        for hit in hits.iter().filter(|hit| !hit.snippets.is_empty()) {
+            self.snippet_screen
+                .update_buffer(format!("{}\n", hit.path.to_string_lossy()).as_str());
+            for snippet in &hit.snippets {
+                self.snippet_screen.update_buffer("    ... ");
+                self.snippet_screen
+                    .insert_highlighted(&snippet.text, &snippet.highlights);
+                self.snippet_screen.update_buffer(" ...\n");
+            }
+            self.snippet_screen.update_buffer("\n");
+        }
+    }
+    /// `on_changed` gets the text of the entry once the user paused typing for
+    /// SEARCH_DELAY_MS
+    pub fn handle_connect_search_changed<F: Fn(String) + 'static>(&self, on_changed: F) {
+        self.search_entry.set_search_delay(SEARCH_DELAY_MS);
+        self.search_entry
+            .connect_search_changed(move |entry| on_changed(entry.text().to_string()));
+    }
+}
+
+/// `text` without the bytes of `span`, the rest of the query is left untouched apart from the
+/// spaces around the cut
+fn remove_span(text: &str, span: Range<usize>) -> String {
+    let (Some(before), Some(after)) = (text.get(..span.start), text.get(span.end..)) else {
+        return text.to_string();
+    };
+    let (before, after) = (before.trim_end(), after.trim_start());
+    if before.is_empty() || after.is_empty() {
+        format!("{}{}", before, after)
+    } else {
+        format!("{} {}", before, after)
     }
-    pub fn handle_connect_search_changed(&self) {}
 }
@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+
+use gtk::{
+    prelude::*, Align, Button, CheckButton, DropDown, Entry, Grid, Label, Orientation, SpinButton,
+    Window,
+};
+
+use crate::config::{Settings, LOG_LEVELS};
+use crate::models::index_rules::parse_patterns;
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// Preferences window editing the TOML config. The view only fills and reads the form, saving
+/// is done by the controller connected to save_button.
+#[derive(Clone)]
+pub struct SettingsView {
+    pub window: Window,
+    pub grid: Grid,
+    pub index_root: Entry,
+    pub storage_dir: Entry,
+    pub log_level: DropDown,
+    pub excluded_patterns: Entry,
+    pub max_file_size: SpinButton,
+    pub incremental_index: CheckButton,
+    pub result_limit: SpinButton,
+    pub page_size: SpinButton,
+    pub load_more: CheckButton,
+    pub status_label: Label,
+    pub save_button: Button,
+    pub cancel_button: Button,
+}
+
+impl SettingsView {
+    pub fn new() -> Self {
+        let window = Window::builder().title("Settings").modal(true).build();
+        let grid = Grid::builder()
+            .margin_top(12)
+            .margin_bottom(12)
+            .margin_start(12)
+            .margin_end(12)
+            .row_spacing(12)
+            .column_spacing(12)
+            .build();
+        let index_root = Entry::new();
+        let storage_dir = Entry::new();
+        let log_level = DropDown::from_strings(&LOG_LEVELS);
+        let excluded_patterns = Entry::builder()
+            .placeholder_text("comma separated globs, e.g. **/target/**")
+            .build();
+        // sizes are edited in MB, 0 disables the limit
+        let max_file_size = SpinButton::with_range(0.0, 4096.0, 1.0);
+        let incremental_index = CheckButton::with_label("re-index only the changed files");
+        let result_limit = SpinButton::with_range(1.0, 100_000.0, 10.0);
+        let page_size = SpinButton::with_range(10.0, 1000.0, 10.0);
+        let load_more = CheckButton::with_label("load more instead of pages");
+        let status_label = Label::new(None);
+        let save_button = Button::with_label("Save");
+        let cancel_button = Button::with_label("Cancel");
+        Self {
+            window,
+            grid,
+            index_root,
+            storage_dir,
+            log_level,
+            excluded_patterns,
+            max_file_size,
+            incremental_index,
+            result_limit,
+            page_size,
+            load_more,
+            status_label,
+            save_button,
+            cancel_button,
+        }
+    }
+
+    pub fn build_ui(&self) {
+        let rows: [(&str, &gtk::Widget); 9] = [
+            ("Default folder to index", self.index_root.upcast_ref()),
+            ("Index storage folder", self.storage_dir.upcast_ref()),
+            ("Log level", self.log_level.upcast_ref()),
+            (
+                "Default excluded patterns",
+                self.excluded_patterns.upcast_ref(),
+            ),
+            (
+                "Default max file size (MB)",
+                self.max_file_size.upcast_ref(),
+            ),
+            ("", self.incremental_index.upcast_ref()),
+            ("Result limit", self.result_limit.upcast_ref()),
+            ("Results per page", self.page_size.upcast_ref()),
+            ("", self.load_more.upcast_ref()),
+        ];
+        for (row, (label, widget)) in rows.iter().enumerate() {
+            let label = Label::builder().label(*label).halign(Align::End).build();
+            self.grid.attach(&label, 0, row as i32, 1, 1);
+            self.grid.attach(*widget, 1, row as i32, 1, 1);
+        }
+        let buttons = gtk::Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(12)
+            .halign(Align::End)
+            .build();
+        buttons.append(&self.cancel_button);
+        buttons.append(&self.save_button);
+        self.grid
+            .attach(&self.status_label, 0, rows.len() as i32, 2, 1);
+        self.grid.attach(&buttons, 0, rows.len() as i32 + 1, 2, 1);
+        self.window.set_child(Some(&self.grid));
+        self.add_style();
+
+        let window = self.window.clone();
+        self.cancel_button.connect_clicked(move |_| window.close());
+    }
+    fn add_style(&self) {
+        self.save_button.add_css_class("suggested-action");
+    }
+
+    pub fn fill(&self, settings: &Settings) {
+        self.index_root
+            .set_text(settings.index_root.to_string_lossy().as_ref());
+        self.storage_dir
+            .set_text(settings.storage_dir.to_string_lossy().as_ref());
+        let level = LOG_LEVELS
+            .iter()
+            .position(|level| *level == settings.log_level)
+            .unwrap_or(1);
+        self.log_level.set_selected(level as u32);
+        self.excluded_patterns
+            .set_text(settings.excluded_patterns.join(", ").as_str());
+        self.max_file_size
+            .set_value((settings.max_file_size / MEGABYTE) as f64);
+        self.incremental_index
+            .set_active(settings.incremental_index);
+        self.result_limit.set_value(settings.result_limit as f64);
+        self.page_size.set_value(settings.page_size as f64);
+        self.load_more.set_active(settings.load_more);
+    }
+
+    /// returns `current` updated with the values of the form
+    pub fn read(&self, current: &Settings) -> Settings {
+        let mut settings = current.clone();
+        settings.index_root = PathBuf::from(self.index_root.text().as_str());
+        settings.storage_dir = PathBuf::from(self.storage_dir.text().as_str());
+        settings.log_level = LOG_LEVELS
+            .get(self.log_level.selected() as usize)
+            .unwrap_or(&"info")
+            .to_string();
+        settings.excluded_patterns = parse_patterns(self.excluded_patterns.text().as_str());
+        settings.max_file_size = self.max_file_size.value_as_int() as u64 * MEGABYTE;
+        settings.incremental_index = self.incremental_index.is_active();
+        settings.result_limit = self.result_limit.value_as_int() as usize;
+        settings.page_size = self.page_size.value_as_int() as usize;
+        settings.load_more = self.load_more.is_active();
+        settings
+    }
+}
@@ -0,0 +1,134 @@
+use std::rc::Rc;
+
+use gtk::{prelude::*, Align, Button, DropDown, Entry, Image, Label, MenuButton, Orientation};
+use gtk::{Popover, PositionType};
+
+use crate::models::filter::Filter;
+
+/// the filters offered by the "Add filter" popover, (label, syntax the value is appended to)
+const FILTER_CHOICES: [(&str, &str); 6] = [
+    ("extension", "ext:"),
+    ("path contains", "path:"),
+    ("modified after", "modified:>"),
+    ("modified before", "modified:<"),
+    ("larger than", "size:>"),
+    ("smaller than", "size:<"),
+];
+
+/// The filters of the current query shown as removable chips, with a popover to add one
+/// without knowing the syntax. The bar only edits text: the query in the entry stays the
+/// reference, see SearchView::handle_filter_bar().
+#[derive(Clone)]
+pub struct FilterBar {
+    pub gtk_box: gtk::Box,
+    chips_box: gtk::Box,
+    pub add_button: MenuButton,
+    field_dropdown: DropDown,
+    value_entry: Entry,
+    apply_button: Button,
+    error_label: Label,
+}
+
+impl FilterBar {
+    pub fn new() -> Self {
+        let gtk_box = gtk::Box::new(Orientation::Horizontal, 6);
+        let chips_box = gtk::Box::new(Orientation::Horizontal, 6);
+        let labels: Vec<&str> = FILTER_CHOICES.iter().map(|(label, _)| *label).collect();
+        let field_dropdown = DropDown::from_strings(&labels);
+        let value_entry = Entry::builder()
+            .placeholder_text("pdf, projects/, 2025-01-01, 2MB...")
+            .build();
+        let apply_button = Button::with_label("Add");
+        let error_label = Label::builder().halign(Align::Start).visible(false).build();
+        error_label.add_css_class("error");
+
+        let form = gtk::Box::new(Orientation::Vertical, 6);
+        let row = gtk::Box::new(Orientation::Horizontal, 6);
+        row.append(&field_dropdown);
+        row.append(&value_entry);
+        row.append(&apply_button);
+        form.append(&row);
+        form.append(&error_label);
+        let popover = Popover::builder()
+            .child(&form)
+            .position(PositionType::Bottom)
+            .build();
+        let add_button = MenuButton::builder()
+            .icon_name("list-add-symbolic")
+            .tooltip_text("Add filter")
+            .popover(&popover)
+            .build();
+        gtk_box.append(&chips_box);
+        gtk_box.append(&add_button);
+
+        Self {
+            gtk_box,
+            chips_box,
+            add_button,
+            field_dropdown,
+            value_entry,
+            apply_button,
+            error_label,
+        }
+    }
+
+    /// replaces the chips, `on_remove` is called with the filter of the chip that was clicked
+    pub fn set_filters(&self, filters: &[Filter], on_remove: Rc<dyn Fn(&Filter)>) {
+        while let Some(child) = self.chips_box.first_child() {
+            self.chips_box.remove(&child);
+        }
+        for filter in filters {
+            let content = gtk::Box::new(Orientation::Horizontal, 4);
+            content.append(&Label::new(Some(&filter.to_string())));
+            content.append(&Image::from_icon_name("window-close-symbolic"));
+            let chip = Button::builder()
+                .child(&content)
+                .tooltip_text("remove this filter")
+                .build();
+            chip.add_css_class("pill");
+            let filter = filter.clone();
+            let on_remove = on_remove.clone();
+            chip.connect_clicked(move |_| on_remove(&filter));
+            self.chips_box.append(&chip);
+        }
+    }
+
+    /// the filter of the form in the query syntax, or why it can't be added
+    fn filter_word(&self) -> Result<String, String> {
+        let Some((_, prefix)) = FILTER_CHOICES.get(self.field_dropdown.selected() as usize) else {
+            return Err(String::from("pick what to filter on"));
+        };
+        let text = self.value_entry.text();
+        let value = text.trim();
+        // a filter is a single word of the query, which has no quoting for it
+        if value.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '(' | ')')) {
+            return Err(String::from(
+                "the value can't contain spaces, quotes or parentheses",
+            ));
+        }
+        let word = format!("{}{}", prefix, value);
+        Filter::parse(&word)?;
+        Ok(word)
+    }
+
+    /// `on_add` gets the filter written in the query syntax, once it has been validated
+    pub fn connect_add<F: Fn(&str) + 'static>(&self, on_add: F) {
+        let bar = self.clone();
+        let add = move || match bar.filter_word() {
+            Ok(word) => {
+                bar.error_label.set_visible(false);
+                bar.value_entry.set_text("");
+                bar.add_button.popdown();
+                on_add(&word);
+            }
+            Err(e) => {
+                bar.error_label.set_text(&e);
+                bar.error_label.set_visible(true);
+            }
+        };
+        let add = Rc::new(add);
+        let add_clone = add.clone();
+        self.apply_button.connect_clicked(move |_| add_clone());
+        self.value_entry.connect_activate(move |_| add());
+    }
+}
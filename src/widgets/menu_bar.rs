@@ -1,5 +1,7 @@
+use std::rc::Rc;
+
 use gtk::glib::subclass::types::ObjectSubclass;
-use gtk::prelude::{BoxExt, PopoverExt, WidgetExt};
+use gtk::prelude::{BoxExt, ButtonExt, PopoverExt, WidgetExt};
 use gtk::AccessibleRole::{Menu, MenuBar, MenuItem};
 use gtk::{Box, Button, HeaderBar, Label, MenuButton, Orientation, Popover, PopoverMenu};
 
@@ -12,6 +14,13 @@ pub struct CustomBar {
     pub header: HeaderBar,
     pub menu_b: MenuButton,
     pub popover: Popover,
+    pub new_button: Button,
+    pub indexes_button: Button,
+    pub open_button: Button,
+    pub settings_button: Button,
+    pub quit_button: Button,
+    /// one button per saved search, see set_saved_searches()
+    pub saved_box: Box,
 }
 
 impl CustomBar {
@@ -20,20 +29,26 @@ impl CustomBar {
             header: HeaderBar::new(),
             menu_b: MenuButton::new(),
             popover: Popover::new(),
+            new_button: Button::with_label("New Index"),
+            indexes_button: Button::with_label("Indexes..."),
+            open_button: Button::with_label("Open index..."),
+            settings_button: Button::with_label("Settings"),
+            quit_button: Button::with_label("Quit"),
+            saved_box: Box::new(gtk::Orientation::Vertical, 0),
             gtk_box_header: Box::new(gtk::Orientation::Horizontal, 0),
             // gtk_box_menu: Box::new(gtk::Orientation::Horizontal, 0),
         }
     }
     pub fn build(&self) {
         // Create menu items
-        let new = Button::with_label("New Index");
-        let settings = Button::with_label("Settings");
-        let quit = Button::with_label("Quit");
         let info = Label::new(Some("TermiRust v.1.0"));
         let menu_box = gtk::Box::new(Orientation::Vertical, 0);
-        menu_box.append(&new);
-        menu_box.append(&settings);
-        menu_box.append(&quit);
+        menu_box.append(&self.new_button);
+        menu_box.append(&self.indexes_button);
+        menu_box.append(&self.open_button);
+        menu_box.append(&self.settings_button);
+        menu_box.append(&self.saved_box);
+        menu_box.append(&self.quit_button);
         menu_box.append(&info);
         self.popover.set_child(Some(&menu_box));
         // self.menu_b.set_active(false);
@@ -43,4 +58,30 @@ impl CustomBar {
         self.gtk_box_header.append(&self.menu_b);
         self.menu_b.set_icon_name("open-menu-symbolic");
     }
+    /// fills the "Saved searches" section, hidden when there is none. `on_run` gets the query
+    /// of the button clicked.
+    pub fn set_saved_searches(&self, queries: &[String], on_run: Rc<dyn Fn(&str)>) {
+        while let Some(child) = self.saved_box.first_child() {
+            self.saved_box.remove(&child);
+        }
+        self.saved_box.set_visible(!queries.is_empty());
+        if queries.is_empty() {
+            return;
+        }
+        let title = Label::new(Some("Saved searches"));
+        title.add_css_class("heading");
+        self.saved_box.append(&title);
+        for query in queries {
+            let button = Button::with_label(query);
+            button.add_css_class("flat");
+            let popover = self.popover.clone();
+            let on_run = on_run.clone();
+            let query = query.clone();
+            button.connect_clicked(move |_| {
+                popover.popdown();
+                on_run(&query);
+            });
+            self.saved_box.append(&button);
+        }
+    }
 }
@@ -1,2 +1,8 @@
+pub mod filter_bar;
 pub mod menu_bar;
+pub mod pager;
+pub mod preview;
+pub mod progress;
+pub mod result_list;
 pub mod screen;
+pub mod suggestions;
@@ -0,0 +1,57 @@
+use gtk::{prelude::*, Align, Button, Label, Orientation};
+
+use crate::models::pagination::ResultPages;
+
+/// Header of the result list: the range displayed and the buttons to move between pages, or
+/// a "load more" button in load more mode.
+#[derive(Clone)]
+pub struct Pager {
+    pub gtk_box: gtk::Box,
+    pub range_label: Label,
+    pub previous_button: Button,
+    pub next_button: Button,
+    pub load_more_button: Button,
+}
+
+impl Pager {
+    pub fn new() -> Self {
+        let gtk_box = gtk::Box::new(Orientation::Horizontal, 6);
+        let range_label = Label::builder().halign(Align::Start).hexpand(true).build();
+        let previous_button = Button::from_icon_name("go-previous-symbolic");
+        previous_button.set_tooltip_text(Some("previous page"));
+        let next_button = Button::from_icon_name("go-next-symbolic");
+        next_button.set_tooltip_text(Some("next page"));
+        let load_more_button = Button::with_label("Load more");
+        gtk_box.append(&range_label);
+        gtk_box.append(&previous_button);
+        gtk_box.append(&next_button);
+        gtk_box.append(&load_more_button);
+
+        let pager = Self {
+            gtk_box,
+            range_label,
+            previous_button,
+            next_button,
+            load_more_button,
+        };
+        pager.clear();
+        pager
+    }
+
+    pub fn update(&self, pages: &ResultPages) {
+        self.range_label.set_text(&pages.label());
+        self.previous_button.set_visible(!pages.load_more);
+        self.next_button.set_visible(!pages.load_more);
+        self.previous_button.set_sensitive(pages.has_previous());
+        self.next_button.set_sensitive(pages.has_next());
+        self.load_more_button
+            .set_visible(pages.load_more && pages.has_next());
+    }
+
+    pub fn clear(&self) {
+        self.range_label.set_text("");
+        self.previous_button.set_visible(false);
+        self.next_button.set_visible(false);
+        self.load_more_button.set_visible(false);
+    }
+}
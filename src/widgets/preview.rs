@@ -0,0 +1,185 @@
+use std::cell::{Cell, RefCell};
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+
+use gtk::glib::{ControlFlow, MainContext, Priority};
+use gtk::{prelude::*, Align, Button, Label, Orientation};
+
+use crate::models::document::load_preview;
+use crate::models::snippet::find_terms;
+use crate::widgets::screen::ScreenOutput;
+
+/// bigger documents are truncated so the TextView stays responsive, and only this much of a
+/// text file is read
+const MAX_PREVIEW_BYTES: usize = 2 * 1024 * 1024;
+
+/// Read-only preview of a document built on ScreenOutput: every query term is highlighted and
+/// the Previous/Next buttons move from one match to the other.
+#[derive(Clone)]
+pub struct PreviewPane {
+    pub gtk_box: gtk::Box,
+    pub screen: ScreenOutput,
+    pub previous_button: Button,
+    pub next_button: Button,
+    pub match_label: Label,
+    matches: Rc<RefCell<Vec<Range<i32>>>>,
+    current: Rc<Cell<usize>>,
+    /// bumped whenever the content changes, a document loaded after something else was shown
+    /// is dropped
+    generation: Rc<Cell<u64>>,
+}
+
+impl PreviewPane {
+    pub fn new() -> Self {
+        let gtk_box = gtk::Box::new(Orientation::Vertical, 6);
+        let screen = ScreenOutput::new();
+        screen.label.set_text("Preview");
+        screen.text_view.set_wrap_mode(gtk::WrapMode::WordChar);
+        let previous_button = Button::from_icon_name("go-up-symbolic");
+        previous_button.set_tooltip_text(Some("previous match"));
+        let next_button = Button::from_icon_name("go-down-symbolic");
+        next_button.set_tooltip_text(Some("next match"));
+        let match_label = Label::new(None);
+        let toolbar = gtk::Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(6)
+            .halign(Align::End)
+            .build();
+        toolbar.append(&match_label);
+        toolbar.append(&previous_button);
+        toolbar.append(&next_button);
+        gtk_box.append(&screen.gtk_box);
+        gtk_box.append(&toolbar);
+
+        let preview = Self {
+            gtk_box,
+            screen,
+            previous_button,
+            next_button,
+            match_label,
+            matches: Rc::new(RefCell::new(Vec::new())),
+            current: Rc::new(Cell::new(0)),
+            generation: Rc::new(Cell::new(0)),
+        };
+        preview.handle_navigation();
+        preview.refresh_navigation();
+        preview
+    }
+
+    fn handle_navigation(&self) {
+        let preview = self.clone();
+        self.previous_button.connect_clicked(move |_| {
+            let count = preview.matches.borrow().len();
+            if count > 0 {
+                preview.go_to((preview.current.get() + count - 1) % count);
+            }
+        });
+        let preview = self.clone();
+        self.next_button.connect_clicked(move |_| {
+            let count = preview.matches.borrow().len();
+            if count > 0 {
+                preview.go_to((preview.current.get() + 1) % count);
+            }
+        });
+    }
+
+    /// loads `path` on a worker thread, extracting pdf or archive entries can take a while,
+    /// then highlights `terms` and scrolls to the first match
+    pub fn show_document(&self, path: &Path, terms: &[String]) {
+        self.show_message("loading...");
+        let generation = self.generation.get();
+        let (sender, receiver) = MainContext::channel::<Result<String, String>>(Priority::DEFAULT);
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            let _ = sender.send(load_preview(&path, MAX_PREVIEW_BYTES));
+        });
+        let (preview, terms) = (self.clone(), terms.to_vec());
+        receiver.attach(None, move |loaded| {
+            if preview.generation.get() == generation {
+                match loaded {
+                    Ok(text) => preview.show_text(&text, &terms),
+                    Err(e) => preview.show_message(&e),
+                }
+            }
+            ControlFlow::Break
+        });
+    }
+
+    pub fn show_text(&self, text: &str, terms: &[String]) {
+        let mut end = text.len().min(MAX_PREVIEW_BYTES);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let text = &text[..end];
+        self.screen.clear_buffer();
+        let matches = self
+            .screen
+            .insert_highlighted(text, &find_terms(text, terms));
+        let has_matches = !matches.is_empty();
+        self.matches.replace(matches);
+        self.current.set(0);
+        if has_matches {
+            self.go_to(0);
+        } else {
+            let buffer = self.screen.text_view.buffer();
+            buffer.place_cursor(&buffer.start_iter());
+            self.screen
+                .text_view
+                .scroll_to_mark(&buffer.get_insert(), 0.0, false, 0.0, 0.0);
+        }
+        self.refresh_navigation();
+        if terms.is_empty() {
+            self.match_label.set_text("");
+        }
+    }
+
+    /// replaces the document shown, or being loaded, with `message`
+    pub fn show_message(&self, message: &str) {
+        self.generation.set(self.generation.get() + 1);
+        self.screen.clear_buffer();
+        self.screen.update_buffer(message);
+        self.matches.replace(Vec::new());
+        self.refresh_navigation();
+        self.match_label.set_text("");
+    }
+
+    fn go_to(&self, position: usize) {
+        let Some(range) = self.matches.borrow().get(position).cloned() else {
+            return;
+        };
+        self.current.set(position);
+        let buffer = self.screen.text_view.buffer();
+        let (start, end) = (
+            buffer.iter_at_offset(range.start),
+            buffer.iter_at_offset(range.end),
+        );
+        // the selection marks the current match among the highlighted ones
+        buffer.select_range(&start, &end);
+        // scrolling to a mark waits for the new text to be laid out, unlike scroll_to_iter
+        let mark = match buffer.mark("current-match") {
+            Some(mark) => {
+                buffer.move_mark(&mark, &start);
+                mark
+            }
+            None => buffer.create_mark(Some("current-match"), &start, true),
+        };
+        self.screen
+            .text_view
+            .scroll_to_mark(&mark, 0.1, true, 0.0, 0.3);
+        self.refresh_navigation();
+    }
+
+    fn refresh_navigation(&self) {
+        let count = self.matches.borrow().len();
+        self.previous_button.set_sensitive(count > 1);
+        self.next_button.set_sensitive(count > 1);
+        if count == 0 {
+            self.match_label.set_text("no match");
+        } else {
+            self.match_label
+                .set_text(format!("{} / {}", self.current.get() + 1, count).as_str());
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use gtk::{
+    prelude::{BoxExt, WidgetExt},
+    Align, Label, Orientation, ProgressBar,
+};
+
+use crate::models::index_job::{IndexSummary, ProgressSnapshot};
+use crate::utils::{format_bytes, format_duration};
+
+/// progress bar and status label displayed under the index button while a folder is indexed
+#[derive(Clone)]
+pub struct IndexProgressBar {
+    pub gtk_box: gtk::Box,
+    pub progress_bar: ProgressBar,
+    pub status_label: Label,
+}
+
+impl IndexProgressBar {
+    pub fn new() -> Self {
+        let gtk_box = gtk::Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .halign(Align::Fill)
+            .visible(false)
+            .build();
+        let progress_bar = ProgressBar::builder().show_text(true).build();
+        let status_label = Label::builder()
+            .ellipsize(gtk::pango::EllipsizeMode::Middle)
+            .max_width_chars(50)
+            .build();
+        gtk_box.append(&progress_bar);
+        gtk_box.append(&status_label);
+        Self {
+            gtk_box,
+            progress_bar,
+            status_label,
+        }
+    }
+    pub fn start(&self) {
+        self.gtk_box.set_visible(true);
+        self.progress_bar.set_fraction(0.0);
+        self.progress_bar.set_text(Some("scanning folder..."));
+        self.status_label.set_text("");
+    }
+    pub fn set_discovered(&self, files: usize) {
+        self.progress_bar
+            .set_text(Some(format!("0 / {} files", files).as_str()));
+    }
+    pub fn update(&self, snapshot: &ProgressSnapshot) {
+        self.progress_bar.set_fraction(snapshot.fraction());
+        let eta = snapshot
+            .eta()
+            .map(|eta| format!(" - ETA {}", format_duration(eta)))
+            .unwrap_or_default();
+        self.progress_bar.set_text(Some(
+            format!(
+                "{} / {} files - {} / {}{}",
+                snapshot.processed,
+                snapshot.total,
+                format_bytes(snapshot.bytes_read),
+                format_bytes(snapshot.total_bytes),
+                eta
+            )
+            .as_str(),
+        ));
+        self.status_label
+            .set_text(snapshot.current_file.to_string_lossy().as_ref());
+    }
+    pub fn set_paused(&self, paused: bool) {
+        if paused {
+            self.status_label.set_text("paused");
+        }
+    }
+    pub fn cancelled(&self) {
+        self.progress_bar.set_text(Some("cancelled"));
+        self.status_label
+            .set_text("indexing cancelled, the current index is unchanged");
+    }
+    pub fn finish(&self, summary: &IndexSummary) {
+        self.progress_bar.set_fraction(1.0);
+        self.progress_bar.set_text(Some("done"));
+        let mut status = format!(
+            "indexed {} files ({}) in {}",
+            summary.files,
+            format_bytes(summary.bytes),
+            format_duration(summary.elapsed)
+        );
+        if let Some(changes) = &summary.changes {
+            status = format!("{}: {}", status, changes);
+        }
+        if summary.skipped > 0 {
+            status = format!("{}, {} skipped", status, summary.skipped);
+        }
+        self.status_label.set_text(status.as_str());
+    }
+}
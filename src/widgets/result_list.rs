@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+
+use gtk::gio::{ListStore, Menu};
+use gtk::glib::{BoxedAnyObject, Object, SignalHandlerId};
+use gtk::{
+    gdk, prelude::*, Align, ColumnView, ColumnViewColumn, CustomSorter, EventSequenceState,
+    GestureClick, Label, LevelBar, ListItem, PopoverMenu, ScrolledWindow, SignalListItemFactory,
+    SingleSelection, SortListModel, Widget,
+};
+
+use crate::models::search_model::SearchHit;
+use crate::utils::{format_bytes, format_timestamp};
+use crate::widgets::pager::Pager;
+
+/// what is stored in the list model for each row
+pub struct ResultRow {
+    pub hit: SearchHit,
+    /// score relative to the best hit, between 0 and 1, used for the score bar
+    pub relative_score: f64,
+}
+
+/// Ranked search results displayed as a sortable table: name, folder, score bar, size and
+/// modification date. Clicking a column header sorts on it.
+#[derive(Clone)]
+pub struct ResultList {
+    pub gtk_box: gtk::Box,
+    pub scroll_win: ScrolledWindow,
+    pub column_view: ColumnView,
+    pub store: ListStore,
+    pub selection: SingleSelection,
+    /// "1–50 of 12 304" and the page buttons, above the table
+    pub pager: Pager,
+    /// right click menu, its entries trigger the "result.*" actions installed on column_view
+    pub context_menu: PopoverMenu,
+}
+
+impl ResultList {
+    pub fn new() -> Self {
+        let gtk_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let store = ListStore::new::<BoxedAnyObject>();
+        let column_view = ColumnView::builder()
+            .show_column_separators(true)
+            .show_row_separators(true)
+            .build();
+        let sort_model = SortListModel::new(Some(store.clone()), column_view.sorter());
+        let selection = SingleSelection::new(Some(sort_model));
+        column_view.set_model(Some(&selection));
+        let scroll_win = ScrolledWindow::builder()
+            .min_content_width(600)
+            .min_content_height(400)
+            .child(&column_view)
+            .build();
+        let pager = Pager::new();
+        let context_menu = PopoverMenu::from_model(Some(&context_menu_model()));
+        context_menu.set_parent(&column_view);
+        context_menu.set_has_arrow(false);
+        context_menu.set_halign(Align::Start);
+        gtk_box.append(&pager.gtk_box);
+        gtk_box.append(&scroll_win);
+
+        let result_list = Self {
+            gtk_box,
+            scroll_win,
+            column_view,
+            store,
+            selection,
+            pager,
+            context_menu,
+        };
+        result_list.setup_columns();
+        result_list
+    }
+
+    fn setup_columns(&self) {
+        self.add_text_column(
+            "Name",
+            true,
+            |hit| hit.file_name(),
+            |a, b| a.file_name().cmp(&b.file_name()),
+        );
+        self.add_text_column(
+            "Folder",
+            true,
+            |hit| hit.folder(),
+            |a, b| a.folder().cmp(&b.folder()),
+        );
+        self.add_score_column();
+        self.add_text_column(
+            "Size",
+            false,
+            |hit| hit.size.map(format_bytes).unwrap_or_default(),
+            |a, b| a.size.cmp(&b.size),
+        );
+        self.add_text_column(
+            "Modified",
+            false,
+            |hit| hit.modified.map(format_timestamp).unwrap_or_default(),
+            |a, b| a.modified.cmp(&b.modified),
+        );
+    }
+
+    fn add_text_column<T, C>(&self, title: &str, expand: bool, text: T, compare: C)
+    where
+        T: Fn(&SearchHit) -> String + 'static,
+        C: Fn(&SearchHit, &SearchHit) -> Ordering + 'static,
+    {
+        let factory = SignalListItemFactory::new();
+        let result_list = self.clone();
+        factory.connect_setup(move |_, list_item| {
+            let label = Label::builder()
+                .halign(Align::Start)
+                .ellipsize(gtk::pango::EllipsizeMode::Middle)
+                .build();
+            let list_item = as_list_item(list_item);
+            result_list.attach_context_menu(label.upcast_ref(), list_item);
+            list_item.set_child(Some(&label));
+        });
+        factory.connect_bind(move |_, list_item| {
+            let list_item = as_list_item(list_item);
+            if let (Some(row), Some(label)) = (
+                list_item.item().and_downcast::<BoxedAnyObject>(),
+                list_item.child().and_downcast::<Label>(),
+            ) {
+                label.set_text(text(&row.borrow::<ResultRow>().hit).as_str());
+            }
+        });
+        let column = ColumnViewColumn::new(Some(title), Some(factory));
+        column.set_expand(expand);
+        column.set_resizable(true);
+        column.set_sorter(Some(&row_sorter(compare)));
+        self.column_view.append_column(&column);
+    }
+
+    fn add_score_column(&self) {
+        let factory = SignalListItemFactory::new();
+        let result_list = self.clone();
+        factory.connect_setup(move |_, list_item| {
+            let bar = LevelBar::builder()
+                .min_value(0.0)
+                .max_value(1.0)
+                .width_request(80)
+                .valign(Align::Center)
+                .build();
+            let list_item = as_list_item(list_item);
+            result_list.attach_context_menu(bar.upcast_ref(), list_item);
+            list_item.set_child(Some(&bar));
+        });
+        factory.connect_bind(|_, list_item| {
+            let list_item = as_list_item(list_item);
+            if let (Some(row), Some(bar)) = (
+                list_item.item().and_downcast::<BoxedAnyObject>(),
+                list_item.child().and_downcast::<LevelBar>(),
+            ) {
+                let row = row.borrow::<ResultRow>();
+                bar.set_value(row.relative_score);
+                bar.set_tooltip_text(Some(format!("{:.4}", row.hit.score).as_str()));
+            }
+        });
+        let column = ColumnViewColumn::new(Some("Score"), Some(factory));
+        // best score first when the header is clicked once
+        column.set_sorter(Some(&row_sorter(|a, b| b.score.total_cmp(&a.score))));
+        self.column_view.append_column(&column);
+    }
+
+    /// a right click on a cell selects its row and pops the context menu up under the pointer
+    fn attach_context_menu(&self, cell: &Widget, list_item: &ListItem) {
+        let gesture = GestureClick::builder()
+            .button(gdk::BUTTON_SECONDARY)
+            .build();
+        let list_item = list_item.downgrade();
+        let (selection, column_view, context_menu) = (
+            self.selection.clone(),
+            self.column_view.clone(),
+            self.context_menu.clone(),
+        );
+        gesture.connect_pressed(move |gesture, _, x, y| {
+            let (Some(list_item), Some(cell)) = (list_item.upgrade(), gesture.widget()) else {
+                return;
+            };
+            gesture.set_state(EventSequenceState::Claimed);
+            selection.set_selected(list_item.position());
+            if let Some((x, y)) = cell.translate_coordinates(&column_view, x, y) {
+                context_menu.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                context_menu.popup();
+            }
+        });
+        cell.add_controller(gesture);
+    }
+
+    /// replaces the rows with `hits`. The score bars are relative to `top_score`, the best
+    /// score of the whole search so that they compare across pages.
+    pub fn set_results(&self, hits: Vec<SearchHit>, top_score: f32) {
+        let rows: Vec<BoxedAnyObject> = hits
+            .into_iter()
+            .map(|hit| {
+                let relative_score = if top_score > 0.0 {
+                    (hit.score / top_score) as f64
+                } else {
+                    0.0
+                };
+                BoxedAnyObject::new(ResultRow {
+                    hit,
+                    relative_score,
+                })
+            })
+            .collect();
+        self.store.remove_all();
+        self.store.extend_from_slice(&rows);
+    }
+    pub fn clear(&self) {
+        self.store.remove_all();
+        self.pager.clear();
+    }
+    /// the hit displayed at `position` of the (sorted) view
+    pub fn hit_at(&self, position: u32) -> Option<SearchHit> {
+        self.selection
+            .item(position)
+            .and_downcast::<BoxedAnyObject>()
+            .map(|row| row.borrow::<ResultRow>().hit.clone())
+    }
+    pub fn selected_hit(&self) -> Option<SearchHit> {
+        self.hit_at(self.selection.selected())
+    }
+    /// calls `callback` on double click or enter on a row
+    pub fn connect_row_activated<F: Fn(&SearchHit) + 'static>(
+        &self,
+        callback: F,
+    ) -> SignalHandlerId {
+        let result_list = self.clone();
+        self.column_view.connect_activate(move |_, position| {
+            if let Some(hit) = result_list.hit_at(position) {
+                callback(&hit);
+            }
+        })
+    }
+}
+
+fn context_menu_model() -> Menu {
+    let menu = Menu::new();
+    menu.append(Some("Open"), Some("result.open"));
+    menu.append(Some("Open with..."), Some("result.open-with"));
+    menu.append(Some("Open containing folder"), Some("result.reveal"));
+    let copy_section = Menu::new();
+    copy_section.append(Some("Copy path"), Some("result.copy-path"));
+    copy_section.append(Some("Copy as file:// URI"), Some("result.copy-uri"));
+    menu.append_section(None, &copy_section);
+    menu
+}
+
+fn as_list_item(object: &Object) -> &ListItem {
+    object
+        .downcast_ref::<ListItem>()
+        .expect("the factory items should be ListItems")
+}
+
+fn row_sorter<C>(compare: C) -> CustomSorter
+where
+    C: Fn(&SearchHit, &SearchHit) -> Ordering + 'static,
+{
+    CustomSorter::new(move |a, b| {
+        match (
+            a.downcast_ref::<BoxedAnyObject>(),
+            b.downcast_ref::<BoxedAnyObject>(),
+        ) {
+            (Some(a), Some(b)) => {
+                compare(&a.borrow::<ResultRow>().hit, &b.borrow::<ResultRow>().hit).into()
+            }
+            _ => gtk::Ordering::Equal,
+        }
+    })
+}
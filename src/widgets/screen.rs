@@ -1,5 +1,7 @@
+use std::ops::Range;
+
 use gtk::{
-    prelude::{BoxExt, TextBufferExt, TextViewExt, WidgetExt},
+    prelude::{BoxExt, TextBufferExt, TextTagExt, TextViewExt, WidgetExt},
     Label, ScrolledWindow, TextBuffer, TextTag, TextTagTable, TextView,
 };
 #[derive(Clone)]
@@ -26,8 +28,14 @@ impl ScreenOutput {
         // text_view.set_margin_end(2);
         // text_view.set_margin_start(2);
 
-        let text_buffer = TextBuffer::new(Some(&TextTagTable::new()));
-        let text_tag = TextTag::new(None);
+        // text_tag highlights the query terms, see insert_highlighted()
+        let text_tag = TextTag::new(Some("highlight"));
+        text_tag.set_weight(700);
+        text_tag.set_background(Some("#f6d32d"));
+        text_tag.set_foreground(Some("#000000"));
+        let tag_table = TextTagTable::new();
+        tag_table.add(&text_tag);
+        let text_buffer = TextBuffer::new(Some(&tag_table));
         let label = Label::new(Some("Command Output"));
         text_view.set_buffer(Some(&text_buffer));
 
@@ -52,6 +60,33 @@ impl ScreenOutput {
         let iter = &mut buffer.end_iter();
         buffer.insert(iter, &data)
     }
+    /// appends `data` and applies text_tag on `highlights`, sorted byte ranges of `data`.
+    /// Returns the highlighted ranges as buffer offsets.
+    pub fn insert_highlighted(&self, data: &str, highlights: &[Range<usize>]) -> Vec<Range<i32>> {
+        let buffer = self.text_view.buffer();
+        let offset = buffer.end_iter().offset();
+        buffer.insert(&mut buffer.end_iter(), data);
+        // buffer offsets count characters, not bytes. The ranges are sorted so the characters
+        // are counted in a single pass over `data`
+        let (mut byte, mut chars) = (0, 0);
+        let mut char_offset = |target: usize| {
+            chars += data[byte..target].chars().count() as i32;
+            byte = target;
+            offset + chars
+        };
+        let mut offsets = Vec::with_capacity(highlights.len());
+        for range in highlights {
+            let start = char_offset(range.start);
+            let end = char_offset(range.end);
+            buffer.apply_tag(
+                &self.text_tag,
+                &buffer.iter_at_offset(start),
+                &buffer.iter_at_offset(end),
+            );
+            offsets.push(start..end);
+        }
+        offsets
+    }
     pub fn clear_buffer(&self) {
         self.text_view.buffer().set_text("");
     }
@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::{prelude::*, Align, Image, Label, ListBox, Orientation, Popover, PositionType};
+
+use crate::models::history::Suggestion;
+
+/// A dropdown of past and saved queries attached under an entry. It never takes the focus so
+/// the user keeps typing while it is shown.
+#[derive(Clone)]
+pub struct SuggestionPopover {
+    pub popover: Popover,
+    list_box: ListBox,
+    /// the query of each row, in order
+    queries: Rc<RefCell<Vec<String>>>,
+}
+
+impl SuggestionPopover {
+    pub fn new(parent: &impl IsA<gtk::Widget>) -> Self {
+        let list_box = ListBox::new();
+        list_box.set_activate_on_single_click(true);
+        let popover = Popover::builder()
+            .child(&list_box)
+            .position(PositionType::Bottom)
+            .has_arrow(false)
+            .autohide(false)
+            .can_focus(false)
+            .halign(Align::Start)
+            .build();
+        popover.set_parent(parent);
+        Self {
+            popover,
+            list_box,
+            queries: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// `on_pick` gets the query of the row clicked
+    pub fn connect_pick<F: Fn(&str) + 'static>(&self, on_pick: F) {
+        let popover = self.popover.clone();
+        let queries = self.queries.clone();
+        self.list_box.connect_row_activated(move |_, row| {
+            let query = queries.borrow().get(row.index() as usize).cloned();
+            if let Some(query) = query {
+                popover.popdown();
+                on_pick(&query);
+            }
+        });
+    }
+
+    /// replaces the rows and shows the popover, or hides it when there is nothing to offer
+    pub fn show(&self, suggestions: &[Suggestion]) {
+        while let Some(child) = self.list_box.first_child() {
+            self.list_box.remove(&child);
+        }
+        self.queries.replace(
+            suggestions
+                .iter()
+                .map(|suggestion| suggestion.query.clone())
+                .collect(),
+        );
+        if suggestions.is_empty() {
+            self.popover.popdown();
+            return;
+        }
+        for suggestion in suggestions {
+            let row = gtk::Box::new(Orientation::Horizontal, 6);
+            let icon = if suggestion.pinned {
+                "starred-symbolic"
+            } else {
+                "document-open-recent-symbolic"
+            };
+            row.append(&Image::from_icon_name(icon));
+            row.append(&Label::new(Some(&suggestion.query)));
+            self.list_box.append(&row);
+        }
+        self.popover.popup();
+    }
+
+    pub fn hide(&self) {
+        self.popover.popdown();
+    }
+}